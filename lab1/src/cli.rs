@@ -0,0 +1,345 @@
+use crate::tests_helper::fuzz_tests::{start_differential_fuzzer, start_fuzzer};
+use crate::tests_helper::meta_tests::start_meta_tests;
+use crate::utils::confluence::start_confluence_report;
+use crate::utils::interreduction::start_interreduction;
+use crate::utils::invariants::{start_invariant_check, start_separation};
+use crate::utils::knuth_bendix::{start_knuth_bendix, CompletionLimits};
+use crate::utils::loops::start_loop_finder;
+use crate::utils::normal_forms::{start_equivalence_class, start_normal_forms};
+use crate::utils::normalizer::{start_normalize, start_normalizer_benchmark};
+use crate::utils::redundancy::start_redundancy_report;
+use crate::utils::rules_additioner::{start_graph_writer, start_rules_additioner};
+use crate::utils::srs_file::{ALPHABET_PATH, RULES_PATH};
+use crate::utils::termination::start_termination_prover;
+use crate::utils::word_problem::{start_equal, WordProblemLimits};
+use csv::{Writer, WriterBuilder};
+use log::error;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io::Stdout;
+use std::thread;
+
+const USAGE: &str = "\
+usage: lab1 <command> [options]
+
+commands:
+  normalize <word>    normal form of a word        [paths] [format] --max-steps
+  complete            Knuth-Bendix completion      [paths] [format] [completion]
+  interreduce         reduced system with a log    [paths] [format] --max-steps
+  confluence          critical pairs report        [paths] [format]
+  normal-forms        irreducible words            [paths] [format] --max-len
+  class <word>        words equal to <word>        [paths] [format] --max-len
+  equal <u> <v>       decide u = v with a proof    [paths] [format] --max-word-len --max-states
+  redundant           rules implied by the others  [paths] [format] --max-word-len --max-states
+  invariant <weights> check a linear weight        [paths] [format]
+  separate <u> <v>    invariant telling u, v apart [paths] [format]
+  termination         search a reduction ordering  [paths]
+  loops               search a looping derivation  [paths]
+  benchmark           compare normalizers          [paths] [completion] --seed
+  graph               normal form tree as DOT      [paths] --max-len --output --labels
+  add-rules           brute-force rule addition    [paths] --output --resume --max-steps
+  fuzz                random rewriting to CSV      [paths] --tests --seed --output --completed --max-steps
+  meta                invariant tests              [paths] --tests --seed
+
+options:
+  --rules <path>          rules file (data/rules.txt)
+  --alphabet <path>       alphabet file (data/alphabet.txt)
+  --format text|csv       output format (text)
+  --max-rules <n>         completion: rules limit
+  --max-rule-len <n>      completion: rule length limit
+  --max-steps <n>         rewrite steps limit
+  --max-len <n>           word length, overrides the alphabet file
+  --max-word-len <n>      word problem: longest word searched
+  --max-states <n>        word problem: words visited
+  --tests <n>             number of random tests
+  --seed <n>              random seed, FUZZ_SEED if not given, printed at startup
+  --output <path>         output file
+  --resume <path>         checkpoint written by an interrupted add-rules
+  --completed <path>      fuzz: check normal forms under this completed system
+  --labels                graph: label reducible nodes with the rule that applies";
+
+const SEED_VAR: &str = "FUZZ_SEED";
+const PATHS: [&str; 2] = ["--rules", "--alphabet"];
+const COMPLETION: [&str; 3] = ["--max-rules", "--max-rule-len", "--max-steps"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Text,
+    Csv,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    pub(crate) rules_path: String,
+    pub(crate) alphabet_path: String,
+    pub(crate) output_path: Option<String>,
+    pub(crate) resume_path: Option<String>,
+    pub(crate) completed_path: Option<String>,
+    pub(crate) format: Format,
+    pub(crate) completion: CompletionLimits,
+    pub(crate) word_problem: WordProblemLimits,
+    pub(crate) max_len: Option<usize>,
+    pub(crate) tests_count: Option<usize>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) labels: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            rules_path: RULES_PATH.to_string(),
+            alphabet_path: ALPHABET_PATH.to_string(),
+            output_path: None,
+            resume_path: None,
+            completed_path: None,
+            format: Format::Text,
+            completion: CompletionLimits::default(),
+            word_problem: WordProblemLimits::default(),
+            max_len: None,
+            tests_count: None,
+            seed: None,
+            labels: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Command {
+    Normalize(String),
+    Complete,
+    Interreduce,
+    Confluence,
+    NormalForms,
+    Class(String),
+    Equal(String, String),
+    Redundant,
+    Invariant(String),
+    Separate(String, String),
+    Termination,
+    Loops,
+    Benchmark,
+    Graph,
+    AddRules,
+    Fuzz,
+    Meta,
+}
+
+fn command_options(name: &str) -> Option<(usize, Vec<&'static str>)> {
+    let format = ["--format"];
+    let (arity, options): (usize, Vec<&str>) = match name {
+        "normalize" => (1, [&PATHS[..], &format, &["--max-steps"]].concat()),
+        "complete" => (0, [&PATHS[..], &format, &COMPLETION].concat()),
+        "interreduce" => (0, [&PATHS[..], &format, &["--max-steps"]].concat()),
+        "confluence" => (0, [&PATHS[..], &format].concat()),
+        "normal-forms" | "class" => (
+            usize::from(name == "class"),
+            [&PATHS[..], &format, &["--max-len"]].concat(),
+        ),
+        "equal" => (2, [&PATHS[..], &format, &["--max-word-len", "--max-states"]].concat()),
+        "redundant" => (0, [&PATHS[..], &format, &["--max-word-len", "--max-states"]].concat()),
+        "invariant" => (1, [&PATHS[..], &format].concat()),
+        "separate" => (2, [&PATHS[..], &format].concat()),
+        "termination" | "loops" => (0, PATHS.to_vec()),
+        "add-rules" => (0, [&PATHS[..], &["--output", "--resume", "--max-steps"]].concat()),
+        "benchmark" => (0, [&PATHS[..], &COMPLETION, &["--seed"]].concat()),
+        "graph" => (0, [&PATHS[..], &["--max-len", "--output", "--labels"]].concat()),
+        "fuzz" => (0, [&PATHS[..], &["--tests", "--seed", "--output", "--completed", "--max-steps"]].concat()),
+        "meta" => (0, [&PATHS[..], &["--tests", "--seed"]].concat()),
+        _ => return None,
+    };
+    Some((arity, options))
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("invalid value `{value}` for {name}: {e}"))
+}
+
+pub(crate) fn parse_args(args: &[String]) -> Result<(Command, Options), String> {
+    let Some((name, rest)) = args.split_first() else {
+        return Err("missing command".to_string());
+    };
+    let Some((arity, accepted)) = command_options(name) else {
+        return Err(format!("unknown command `{name}`"));
+    };
+
+    let mut options = Options::default();
+    let mut positional = vec![];
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg.clone());
+            continue;
+        }
+        if !accepted.contains(&arg.as_str()) {
+            return Err(format!("`{name}` does not take {arg}"));
+        }
+        // flags take no value
+        if arg == "--labels" {
+            options.labels = true;
+            continue;
+        }
+        let Some(value) = rest.next() else {
+            return Err(format!("{arg} needs a value"));
+        };
+        match arg.as_str() {
+            "--rules" => options.rules_path = value.clone(),
+            "--alphabet" => options.alphabet_path = value.clone(),
+            "--output" => options.output_path = Some(value.clone()),
+            "--resume" => options.resume_path = Some(value.clone()),
+            "--completed" => options.completed_path = Some(value.clone()),
+            "--format" => {
+                options.format = match value.as_str() {
+                    "text" => Format::Text,
+                    "csv" => Format::Csv,
+                    _ => return Err(format!("unknown format `{value}`, expected text or csv")),
+                }
+            }
+            "--max-rules" => options.completion.max_rules = number(arg, value)?,
+            "--max-rule-len" => options.completion.max_rule_len = number(arg, value)?,
+            "--max-steps" => options.completion.max_steps = number(arg, value)?,
+            "--max-len" => options.max_len = Some(number(arg, value)?),
+            "--max-word-len" => options.word_problem.max_word_len = number(arg, value)?,
+            "--max-states" => options.word_problem.max_states = number(arg, value)?,
+            "--tests" => options.tests_count = Some(number(arg, value)?),
+            "--seed" => options.seed = Some(number(arg, value)?),
+            _ => unreachable!("accepted options are all handled"),
+        }
+    }
+
+    // a seed printed by an earlier run can also come back through the environment
+    if accepted.contains(&"--seed")
+        && options.seed.is_none()
+        && let Ok(value) = std::env::var(SEED_VAR)
+    {
+        options.seed = Some(number(SEED_VAR, &value)?);
+    }
+
+    if positional.len() != arity {
+        return Err(format!(
+            "`{name}` takes {arity} argument(s), got {}",
+            positional.len()
+        ));
+    }
+    // "." and "ε" stand for the empty word like in the rules file
+    let mut words = positional
+        .into_iter()
+        .map(|word| if word == "." || word == "ε" { String::new() } else { word });
+    let command = match name.as_str() {
+        "normalize" => Command::Normalize(words.next().unwrap()),
+        "complete" => Command::Complete,
+        "interreduce" => Command::Interreduce,
+        "confluence" => Command::Confluence,
+        "normal-forms" => Command::NormalForms,
+        "class" => Command::Class(words.next().unwrap()),
+        "equal" => Command::Equal(words.next().unwrap(), words.next().unwrap()),
+        "redundant" => Command::Redundant,
+        "invariant" => Command::Invariant(words.next().unwrap()),
+        "separate" => Command::Separate(words.next().unwrap(), words.next().unwrap()),
+        "termination" => Command::Termination,
+        "loops" => Command::Loops,
+        "benchmark" => Command::Benchmark,
+        "graph" => Command::Graph,
+        "add-rules" => Command::AddRules,
+        "fuzz" => Command::Fuzz,
+        "meta" => Command::Meta,
+        _ => unreachable!("commands are checked above"),
+    };
+    Ok((command, options))
+}
+
+// every random run prints its seed, so passing it back with --seed replays the same words
+pub(crate) fn seeded_rng(options: &Options) -> StdRng {
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!("Seed: {seed}");
+    StdRng::seed_from_u64(seed)
+}
+
+pub(crate) fn csv_writer() -> Writer<Stdout> {
+    WriterBuilder::new().delimiter(b';').from_writer(std::io::stdout())
+}
+
+pub(crate) fn run(args: &[String]) -> i32 {
+    if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return 0;
+    }
+    let (command, options) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return 2;
+        }
+    };
+
+    let result = match command {
+        Command::Normalize(word) => start_normalize(&word, &options),
+        Command::Complete => start_knuth_bendix(&options),
+        Command::Interreduce => start_interreduction(&options),
+        Command::Confluence => start_confluence_report(&options),
+        Command::NormalForms => start_normal_forms(&options),
+        Command::Class(word) => start_equivalence_class(&word, &options),
+        Command::Equal(u, v) => start_equal(&u, &v, &options),
+        Command::Redundant => start_redundancy_report(&options),
+        Command::Invariant(weights) => start_invariant_check(&weights, &options),
+        Command::Separate(u, v) => start_separation(&u, &v, &options),
+        Command::Termination => start_termination_prover(&options),
+        Command::Loops => start_loop_finder(&options),
+        Command::Benchmark => start_normalizer_benchmark(&options),
+        Command::Graph => start_graph_writer(&options),
+        Command::AddRules => {
+            // the brute-force search recurses deeply in get_normal_forms
+            let stack_size = 1024 * 1024 * 1024;
+            let handler = thread::Builder::new()
+                .stack_size(stack_size)
+                .spawn(move || start_rules_additioner(&options));
+            match handler.map(|handler| handler.join()) {
+                Ok(Ok(result)) => result,
+                Ok(Err(_)) => Err("Completion thread panicked".to_string()),
+                Err(e) => Err(format!("Cannot start the completion thread: {e}")),
+            }
+        }
+        Command::Fuzz => match &options.completed_path {
+            Some(completed_path) => start_differential_fuzzer(&options, completed_path),
+            None => start_fuzzer(&options),
+        },
+        Command::Meta => start_meta_tests(&options),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("{e}");
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn labels_is_a_flag_of_graph() {
+        let (command, options) = parse_args(&args("graph --labels --max-len 3")).unwrap();
+        assert_eq!(command, Command::Graph);
+        assert!(options.labels);
+        assert_eq!(options.max_len, Some(3));
+        assert!(!parse_args(&args("graph")).unwrap().1.labels);
+        assert!(parse_args(&args("complete --labels")).is_err());
+    }
+
+    #[test]
+    fn failed_commands_exit_with_one() {
+        assert_eq!(run(&args("termination --rules missing/rules.txt --alphabet missing/alphabet.txt")), 1);
+        assert_eq!(run(&args("complete --bogus")), 2);
+        assert_eq!(run(&args("--help")), 0);
+    }
+}
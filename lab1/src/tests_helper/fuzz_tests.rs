@@ -1,108 +1,48 @@
 use csv::{WriterBuilder};
 use log::{error, info};
-use rand::Rng;
+use crate::cli::{seeded_rng, Options};
+use crate::tests_helper::shrink::{shrink, Derivation};
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::srs::Srs;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// the first tests start from the words up to this length where left sides overlap
+const OVERLAP_LEN: usize = 8;
 
-struct Rule {
-    left_rule: String,
-    right_rule: String,
-}
 struct Fuzzer {
     tests_count: usize,
     min_str_len: usize,
     max_str_len: usize,
     max_rewrites: usize,
     alphabet: Vec<char>,
-    rules: Vec<Rule>,
-    rnd: rand::rngs::ThreadRng,
+    srs: Srs,
+    overlaps: Vec<String>,
+    rnd: StdRng,
 }
 
 impl Fuzzer {
-    fn new() -> Self {
-        Self {
-            tests_count: 3333,
+    fn new(options: &Options) -> Result<Self, String> {
+        let srs = Srs::load(&options.rules_path, &options.alphabet_path)?;
+        info!("Fuzzing {} rules from {}", srs.left_rules.len(), options.rules_path);
+        let fuzzer = Self {
+            tests_count: options.tests_count.unwrap_or(3333),
             min_str_len: 10,
             max_str_len: 100,
             max_rewrites: 50,
-            alphabet: vec!['a', 'b', 'c'],
-            rnd: rand::thread_rng(),
-            rules: vec![
-                Rule {
-                    left_rule: "cb".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "aaa".to_string(),
-                    right_rule: "aa".to_string(),
-                },
-                Rule {
-                    left_rule: "aba".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "cc".to_string(),
-                    right_rule: "ac".to_string(),
-                },
-                Rule {
-                    left_rule: "baa".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "bba".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "bbb".to_string(),
-                    right_rule: "b".to_string(),
-                },
-                Rule {
-                    left_rule: "bbc".to_string(),
-                    right_rule: "c".to_string(),
-                },
-                Rule {
-                    left_rule: "bcc".to_string(),
-                    right_rule: "cc".to_string(),
-                },
-                Rule {
-                    left_rule: "cab".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "cac".to_string(),
-                    right_rule: "cc".to_string(),
-                },
-                Rule {
-                    left_rule: "cac".to_string(),
-                    right_rule: "bab".to_string(),
-                },
-                Rule {
-                    left_rule: "ccc".to_string(),
-                    right_rule: "c".to_string(),
-                },
-                Rule {
-                    left_rule: "babb".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "cabba".to_string(),
-                    right_rule: "baca".to_string(),
-                },
-                Rule {
-                    left_rule: "caab".to_string(),
-                    right_rule: "bb".to_string(),
-                },
-                Rule {
-                    left_rule: "caac".to_string(),
-                    right_rule: "bc".to_string(),
-                },
-                Rule {
-                    left_rule: "aabcaa".to_string(),
-                    right_rule: "a".to_string(),
-                },
-                Rule {
-                    left_rule: "babc".to_string(),
-                    right_rule: "".to_string(),
-                },
-            ],
+            alphabet: srs.symbols.alphabet().to_vec(),
+            rnd: seeded_rng(options),
+            overlaps: srs.overlap_words(OVERLAP_LEN),
+            srs,
+        };
+        info!("Starting with {} words where left sides overlap", fuzzer.overlaps.len());
+        Ok(fuzzer)
+    }
+
+    fn start_word(&mut self, test: usize) -> String {
+        match self.overlaps.get(test) {
+            Some(word) => word.clone(),
+            None => self.gen_string(),
         }
     }
 
@@ -117,39 +57,24 @@ impl Fuzzer {
         string
     }
 
-    fn random_rewrite(&mut self, string: &String) -> (String, usize) {
-        let mut new_string = string.clone();
+    fn random_rewrite(&mut self, string: &str) -> (String, usize, Vec<(usize, usize)>) {
+        let mut new_string = string.to_string();
         let count_rewrites = self.rnd.gen_range(0..self.max_rewrites);
+        let mut steps = vec![];
 
         for _ in 0..count_rewrites {
-            let mut entries = Vec::new();
-            for (rule_id, rule) in self.rules.iter().enumerate() {
-                if rule.left_rule.is_empty() {
-                    continue;
-                }
-                let mut start = 0;
-                while let Some(pos) = new_string[start..].find(&rule.left_rule) {
-                    entries.push((rule_id, start + pos));
-                    start = start + pos + 1;
-
-                    if start >= new_string.len() {
-                        break;
-                    }
-                }
-            }
+            let entries = self.srs.redexes(&new_string);
             if entries.is_empty() {
                 break;
             }
             let rand_rewrite = entries[self.rnd.gen_range(0..entries.len())];
-            new_string.replace_range(
-                rand_rewrite.1..rand_rewrite.1 + self.rules[rand_rewrite.0].left_rule.len(),
-                &self.rules[rand_rewrite.0].right_rule,
-            );
+            new_string = self.srs.rewrite_redex(&new_string, rand_rewrite);
+            steps.push(rand_rewrite);
         }
-        (new_string, count_rewrites)
+        (new_string, count_rewrites, steps)
     }
 
-    fn find_lcs(&self, string1: &String, string2: &String) -> usize {
+    fn find_lcs(&self, string1: &str, string2: &str) -> usize {
         let chars1: Vec<char> = string1.chars().collect();
         let chars2: Vec<char> = string2.chars().collect();
         let m = chars1.len();
@@ -166,29 +91,26 @@ impl Fuzzer {
             }
         }
 
-        let length = dp[m][n];
-        length
+        dp[m][n]
     }
 }
 
-pub fn start_fuzzer() {
-    let mut fuzzer = Fuzzer::new();
+pub fn start_fuzzer(options: &Options) -> Result<(), String> {
+    let mut fuzzer = Fuzzer::new(options)?;
+    let path = options.output_path.as_deref().unwrap_or("data/fuzzer_results.csv");
 
     let mut file = match WriterBuilder::new()
         .delimiter(b';')
-        .from_path("data/fuzzer_results.csv")
+        .from_path(path)
     {
         Ok(f) => {
-            info!("Trying to open file data/fuzzer_results.csv");
+            info!("Trying to open file {path}");
             f
         }
-        Err(e) => {
-            error!("Error while open file {e}");
-            return;
-        }
+        Err(e) => return Err(format!("Error while open file {e}")),
     };
 
-    match file.write_record(&[
+    match file.write_record([
         "original",
         "rewritten",
         "lcs",
@@ -198,19 +120,16 @@ pub fn start_fuzzer() {
     ]) {
         Ok(_) => {
         }
-        Err(e) => {
-            error!("Error while writing to file {e}");
-            return;
-        }
+        Err(e) => return Err(format!("Error while writing to file {e}")),
     }
 
-    for _ in 0..fuzzer.tests_count {
-        let gen_string = fuzzer.gen_string();
-        let (new_string, count_rewrites) = fuzzer.random_rewrite(&gen_string);
+    for test in 0..fuzzer.tests_count {
+        let gen_string = fuzzer.start_word(test);
+        let (new_string, count_rewrites, _) = fuzzer.random_rewrite(&gen_string);
         let lcs = fuzzer.find_lcs(&gen_string, &new_string);
-        match file.write_record(&[
-            &gen_string,
-            &new_string,
+        match file.write_record([
+            &fuzzer.srs.symbols.decode(&gen_string),
+            &fuzzer.srs.symbols.decode(&new_string),
             &lcs.to_string(),
             &(gen_string.len() - lcs).to_string(),
             &(new_string.len() - lcs).to_string(),
@@ -218,12 +137,222 @@ pub fn start_fuzzer() {
         ]) {
             Ok(_) => {
             }
-            Err(e) => {
-                error!("Error while writing to file {e}");
-                return;
+            Err(e) => return Err(format!("Error while writing to file {e}")),
+        }
+    }
+    file.flush().map_err(|e| format!("Error while writing to file {e}"))?;
+    println!("Results saved to {path}");
+    Ok(())
+}
+
+// a word of length 100 has too many derivations to search them all, so normal forms are
+// compared between the leftmost strategy and this many random ones
+const RANDOM_STRATEGIES: usize = 3;
+
+// None if rewriting does not stop within max_steps; the random strategies use a fixed seed,
+// so the result depends only on the word and a shrunk case fails the same way
+fn normal_forms(completed: &RulesAddition, word: &str, max_steps: usize) -> Option<Vec<String>> {
+    let mut rnd = StdRng::seed_from_u64(0);
+    let mut steps = 0;
+    let mut normal_forms = vec![completed.normalize_counted(word, &mut steps, max_steps)?];
+    for _ in 0..RANDOM_STRATEGIES {
+        let mut current = word.to_string();
+        let mut steps = 0;
+        loop {
+            // equations only apply once no rule does, like in the leftmost strategy
+            let redexes = completed.srs.redexes(&current);
+            let equation_steps = if redexes.is_empty() {
+                completed.equation_steps(&current)
+            } else {
+                vec![]
+            };
+            if redexes.is_empty() && equation_steps.is_empty() {
+                break;
             }
+            if steps >= max_steps {
+                return None;
+            }
+            steps += 1;
+            current = if redexes.is_empty() {
+                equation_steps[rnd.gen_range(0..equation_steps.len())].1.clone()
+            } else {
+                completed.srs.rewrite_redex(&current, redexes[rnd.gen_range(0..redexes.len())])
+            };
+        }
+        if !normal_forms.contains(&current) {
+            normal_forms.push(current);
         }
     }
-    file.flush().expect("panic!");
-    println!("Results saved to fuzzer_results.csv");
+    Some(normal_forms)
+}
+
+fn verdict(original: &Option<Vec<String>>, rewritten: &Option<Vec<String>>, max_steps: usize) -> Option<String> {
+    match (original, rewritten) {
+        (None, _) | (_, None) => Some(format!("rewriting does not stop within {max_steps} steps")),
+        (Some(u), Some(v)) if u.len() > 1 || v.len() > 1 => Some("several normal forms".to_string()),
+        (Some(u), Some(v)) if u != v => Some("different normal forms".to_string()),
+        _ => None,
+    }
+}
+
+impl Fuzzer {
+    fn show_forms(&self, forms: &Option<Vec<String>>) -> String {
+        let forms = forms.as_deref().unwrap_or_default();
+        forms.iter().map(|form| self.srs.symbols.decode(form)).collect::<Vec<_>>().join(",")
+    }
+}
+
+// rewrites random words with the original rules and checks that the completed system gives
+// the word and its rewrite one normal form each, and the same one
+pub fn start_differential_fuzzer(options: &Options, completed_path: &str) -> Result<(), String> {
+    let mut fuzzer = Fuzzer::new(options)?;
+    let mut completed = RulesAddition::new();
+    completed.read_letters(&options.alphabet_path);
+    if completed.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    // the completed rules are taken as written, reorienting them could hide a bug
+    let Some(rules) = completed.read_raw_rules(completed_path) else {
+        return Err(format!("Cannot read rules {completed_path}"));
+    };
+    if completed.srs.symbols != fuzzer.srs.symbols {
+        return Err(format!("{completed_path} is over other letters than {}", options.rules_path));
+    }
+    info!("Checking against {} rules from {completed_path}", rules.len());
+    completed.set_rules(rules);
+
+    let path = options.output_path.as_deref().unwrap_or("data/differential_results.csv");
+    let mut file = match WriterBuilder::new().delimiter(b';').from_path(path) {
+        Ok(f) => {
+            info!("Trying to open file {path}");
+            f
+        }
+        Err(e) => return Err(format!("Error while open file {e}")),
+    };
+    let header = [
+        "original",
+        "rewritten",
+        "rewrites_count",
+        "nf_original",
+        "nf_rewritten",
+        "verdict",
+        "reproducer",
+    ];
+    file.write_record(header).map_err(|e| format!("Error while writing to file {e}"))?;
+
+    let max_steps = options.completion.max_steps;
+    let mut bugs = 0;
+    for test in 0..fuzzer.tests_count {
+        let gen_string = fuzzer.start_word(test);
+        let (new_string, count_rewrites, steps) = fuzzer.random_rewrite(&gen_string);
+        let original = normal_forms(&completed, &gen_string, max_steps);
+        let rewritten = normal_forms(&completed, &new_string, max_steps);
+        let bug = verdict(&original, &rewritten, max_steps);
+        let mut reproducer = String::new();
+        if let Some(bug) = &bug {
+            bugs += 1;
+            error!(
+                "Completion bug: {} and {} are equal by the original rules, {bug}",
+                fuzzer.srs.symbols.decode(&gen_string),
+                fuzzer.srs.symbols.decode(&new_string)
+            );
+            let derivation = Derivation {
+                word: gen_string.clone(),
+                steps,
+            };
+            let shrunk = shrink(&fuzzer.srs, &derivation, |u, v| {
+                verdict(&normal_forms(&completed, u, max_steps), &normal_forms(&completed, v, max_steps), max_steps)
+                    .is_some()
+            });
+            reproducer = fuzzer.srs.symbols.decode(&shrunk.proof(&fuzzer.srs).to_string());
+            error!("Shortest reproducer: {reproducer}");
+        }
+        file.write_record([
+            fuzzer.srs.symbols.decode(&gen_string),
+            fuzzer.srs.symbols.decode(&new_string),
+            count_rewrites.to_string(),
+            fuzzer.show_forms(&original),
+            fuzzer.show_forms(&rewritten),
+            bug.unwrap_or_else(|| "ok".to_string()),
+            reproducer,
+        ])
+        .map_err(|e| format!("Error while writing to file {e}"))?;
+    }
+    file.flush().map_err(|e| format!("Error while writing to file {e}"))?;
+    println!("{bugs} of {} pairs break the completed system", fuzzer.tests_count);
+    println!("Results saved to {path}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // fuzzes "ab -> a, ab -> b" against `completed` and returns the verdicts with their reproducers
+    fn verdicts(name: &str, completed: &str) -> Vec<(String, String)> {
+        let dir = std::env::temp_dir().join(format!("lab1-differential-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+        fs::write(path("alphabet.txt"), "6\na\nb\n").unwrap();
+        fs::write(path("rules.txt"), "ab -> a\nab -> b\n").unwrap();
+        fs::write(path("completed.txt"), completed).unwrap();
+        let options = Options {
+            rules_path: path("rules.txt"),
+            alphabet_path: path("alphabet.txt"),
+            output_path: Some(path("results.csv")),
+            tests_count: Some(20),
+            seed: Some(1),
+            ..Options::default()
+        };
+        start_differential_fuzzer(&options, &path("completed.txt")).unwrap();
+
+        let mut reader = csv::ReaderBuilder::new().delimiter(b';').from_path(path("results.csv")).unwrap();
+        let verdicts = reader
+            .records()
+            .map(|record| record.unwrap())
+            .map(|record| (record[5].to_string(), record[6].to_string()))
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        verdicts
+    }
+
+    #[test]
+    fn incomplete_system_is_caught() {
+        let incomplete = verdicts("incomplete", "ab -> a\nab -> b\n");
+        assert_eq!(incomplete.len(), 20);
+        assert!(incomplete.iter().any(|(verdict, _)| verdict != "ok"));
+        // "ab" alone already has two normal forms, so every failing pair shrinks to it
+        for (verdict, reproducer) in &incomplete {
+            assert_eq!(reproducer, if verdict == "ok" { "" } else { "ab" });
+        }
+
+        let complete = verdicts("complete", "b -> a\naa -> a\n");
+        assert!(complete.iter().all(|(verdict, _)| verdict == "ok"));
+    }
+
+    #[test]
+    fn same_seed_writes_the_same_csv() {
+        let dir = std::env::temp_dir().join(format!("lab1-seed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+        fs::write(path("alphabet.txt"), "6\na\nb\n").unwrap();
+        fs::write(path("rules.txt"), "ab -> ba\nbb -> a\n").unwrap();
+        let run = |seed: u64| {
+            let options = Options {
+                rules_path: path("rules.txt"),
+                alphabet_path: path("alphabet.txt"),
+                output_path: Some(path("results.csv")),
+                tests_count: Some(30),
+                seed: Some(seed),
+                ..Options::default()
+            };
+            start_fuzzer(&options).unwrap();
+            fs::read_to_string(path("results.csv")).unwrap()
+        };
+        let first = run(7);
+        assert_eq!(run(7), first);
+        assert_ne!(run(8), first);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,10 +1,16 @@
-use log::{error, info};
+use log::{error, info, warn};
+use crate::cli::{seeded_rng, Options};
+use crate::tests_helper::shrink::{shrink, Derivation};
+use crate::utils::invariants::LinearWeight;
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::srs::Srs;
+use rand::rngs::StdRng;
 use rand::Rng;
 
-struct Rule {
-    left_rule: String,
-    right_rule: String,
-}
+// the first tests start from the words up to this length where left sides overlap
+const OVERLAP_LEN: usize = 8;
+// the new system collapses every letter onto `a`
+const NEW_RULES: [(&str, &str); 2] = [("c", "a"), ("b", "a")];
 
 struct MetaTest {
     tests_count: usize,
@@ -12,108 +18,44 @@ struct MetaTest {
     max_str_len: usize,
     max_rewrites: usize,
     alphabet: Vec<char>,
-    base_rules: Vec<Rule>,
-    new_rules: Vec<Rule>,
-    rnd: rand::rngs::ThreadRng,
+    base: Srs,
+    new: Srs,
+    base_overlaps: Vec<String>,
+    new_overlaps: Vec<String>,
+    rnd: StdRng,
 }
 
 impl MetaTest {
-    fn new() -> Self {
-        Self {
-            tests_count: 3333,
+    fn new(options: &Options) -> Result<Self, String> {
+        let base = Srs::load(&options.rules_path, &options.alphabet_path)?;
+        let mut new_rules = vec![];
+        for (left, right) in NEW_RULES {
+            new_rules.push((base.encode(left)?, base.encode(right)?));
+        }
+        let new = base.with_rules(new_rules)?;
+        Ok(Self {
+            tests_count: options.tests_count.unwrap_or(3333),
             min_str_len: 10,
             max_str_len: 100,
             max_rewrites: 50,
-            alphabet: vec!['a', 'b', 'c'],
-            rnd: rand::thread_rng(),
-            base_rules: vec![
-                Rule {
-                    left_rule: "cb".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "aaa".to_string(),
-                    right_rule: "aa".to_string(),
-                },
-                Rule {
-                    left_rule: "aba".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "cc".to_string(),
-                    right_rule: "ac".to_string(),
-                },
-                Rule {
-                    left_rule: "baa".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "bba".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "bbb".to_string(),
-                    right_rule: "b".to_string(),
-                },
-                Rule {
-                    left_rule: "bbc".to_string(),
-                    right_rule: "c".to_string(),
-                },
-                Rule {
-                    left_rule: "bcc".to_string(),
-                    right_rule: "cc".to_string(),
-                },
-                Rule {
-                    left_rule: "cab".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "cac".to_string(),
-                    right_rule: "cc".to_string(),
-                },
-                Rule {
-                    left_rule: "cac".to_string(),
-                    right_rule: "bab".to_string(),
-                },
-                Rule {
-                    left_rule: "ccc".to_string(),
-                    right_rule: "c".to_string(),
-                },
-                Rule {
-                    left_rule: "babb".to_string(),
-                    right_rule: "ba".to_string(),
-                },
-                Rule {
-                    left_rule: "cabba".to_string(),
-                    right_rule: "baca".to_string(),
-                },
-                Rule {
-                    left_rule: "caab".to_string(),
-                    right_rule: "bb".to_string(),
-                },
-                Rule {
-                    left_rule: "caac".to_string(),
-                    right_rule: "bc".to_string(),
-                },
-                Rule {
-                    left_rule: "aabcaa".to_string(),
-                    right_rule: "a".to_string(),
-                },
-                Rule {
-                    left_rule: "babc".to_string(),
-                    right_rule: "".to_string(),
-                },
-            ],
-            new_rules: vec![
-                Rule {
-                    left_rule: "c".to_string(),
-                    right_rule: "a".to_string(),
-                },
-                Rule {
-                    left_rule: "b".to_string(),
-                    right_rule: "a".to_string(),
-                },
-            ],
+            alphabet: base.symbols.alphabet().to_vec(),
+            rnd: seeded_rng(options),
+            base_overlaps: base.overlap_words(OVERLAP_LEN),
+            new_overlaps: new.overlap_words(OVERLAP_LEN),
+            base,
+            new,
+        })
+    }
+
+    fn start_word(&mut self, test: usize, base: bool) -> String {
+        let overlaps = if base {
+            &self.base_overlaps
+        } else {
+            &self.new_overlaps
+        };
+        match overlaps.get(test) {
+            Some(word) => word.clone(),
+            None => self.gen_string(),
         }
     }
 
@@ -127,42 +69,37 @@ impl MetaTest {
 
         string
     }
-    fn random_rewrite(&mut self, string: &String, base: bool) -> Option<String> {
-        let mut new_string = string.clone();
+    fn random_rewrite(&mut self, string: &str, base: bool) -> Option<(String, Derivation)> {
+        let mut new_string = string.to_string();
         let count_rewrites = self.rnd.gen_range(0..self.max_rewrites);
 
-        let rules = if base {
-            &self.base_rules
-        } else {
-            &self.new_rules
+        let srs = if base { &self.base } else { &self.new };
+        let mut derivation = Derivation {
+            word: string.to_string(),
+            steps: vec![],
         };
 
         for _ in 0..count_rewrites {
-            let mut entries = Vec::new();
-            for (rule_id, rule) in rules.iter().enumerate() {
-                if rule.left_rule.is_empty() {
-                    continue;
-                }
-                let mut start = 0;
-                while let Some(pos) = new_string[start..].find(&rule.left_rule) {
-                    entries.push((rule_id, start + pos));
-                    start = start + pos + 1;
-
-                    if start >= new_string.len() {
-                        break;
-                    }
-                }
-            }
+            let entries = srs.redexes(&new_string);
             if entries.is_empty() {
                 break;
             }
             let rand_rewrite = entries[self.rnd.gen_range(0..entries.len())];
-            new_string.replace_range(
-                rand_rewrite.1..rand_rewrite.1 + rules[rand_rewrite.0].left_rule.len(),
-                &rules[rand_rewrite.0].right_rule,
-            );
+            new_string = srs.rewrite_redex(&new_string, rand_rewrite);
+            derivation.steps.push(rand_rewrite);
         }
-        Some(new_string)
+        Some((new_string, derivation))
+    }
+
+    fn srs(&self, base: bool) -> &Srs {
+        if base { &self.base } else { &self.new }
+    }
+
+    // the shortest word and derivation that still break the invariant
+    fn log_reproducer(&self, derivation: &Derivation, base: bool, breaks: impl Fn(&str, &str) -> bool) {
+        let srs = self.srs(base);
+        let shrunk = shrink(srs, derivation, breaks);
+        error!("Shortest reproducer: {}", srs.symbols.decode(&shrunk.proof(srs).to_string()));
     }
 
     fn capitalize(&self, s: &str) -> String {
@@ -173,83 +110,84 @@ impl MetaTest {
         }
     }
 
-    fn count_parikh_measure(&self, word: &str) -> usize {
-        word.chars().filter(|&ch| ch == 'a').count()
-            + word.chars().filter(|&ch| ch == 'b').count()
-            + 2 * word.chars().filter(|&ch| ch == 'c').count()
-    }
-
-    fn start_weighted_parikh_measure_invariant_tests(&mut self, base: bool) {
-        let system = if base {
-            "base".to_string()
-        } else {
-            "new".to_string()
+    // the exact check over the rules decides the invariant, random derivations cross-check it
+    fn start_weight_invariant_tests(&mut self, base: bool, weights: &str, measure: &str) {
+        let system = if base { "base" } else { "new" };
+        let symbols = self.srs(base).symbols.clone();
+        let weight = match LinearWeight::parse(weights, &symbols) {
+            Ok(weight) => weight,
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
         };
         info!(
-            "Testing {} system (strictly decreasing Parikh measure F(w) = #a + #b + 2*#c)...",
-            system
+            "Testing {system} system (decreasing {measure}, W(w) = {})...",
+            symbols.decode(&weight.to_string())
         );
 
-        for _ in 0..self.tests_count {
-            let mut gen_string = self.gen_string();
-            let mut p_measure = self.count_parikh_measure(&gen_string);
+        let report = self.srs(base).verify_invariant(&weight);
+        for i in report.breaking() {
+            let ((left, right), w_left, w_right) = &report.rules[i];
+            let rule = symbols.decode(&RulesAddition::rule_line(left, right));
+            error!("Rule {rule} increases W ({w_left} → {w_right})");
+        }
+        info!("Exact check: {}", report.verdict());
+
+        let mut failures = 0;
+        for test in 0..self.tests_count {
+            let mut gen_string = self.start_word(test, base);
+            let mut value = weight.value(&gen_string);
             for _ in 0..self.max_rewrites {
-                if let Some(new) = self.random_rewrite(&gen_string, base) {
-                    let new_p_measure = self.count_parikh_measure(&new);
-                    if new_p_measure > p_measure {
+                if let Some((new, derivation)) = self.random_rewrite(&gen_string, base) {
+                    let new_value = weight.value(&new);
+                    if new_value > value {
+                        failures += 1;
                         error!(
-                            "Invariant fail: {gen_string} → {new} (#c {p_measure} → {new_p_measure})"
+                            "Invariant fail: {} → {} (W {value} → {new_value})",
+                            symbols.decode(&gen_string),
+                            symbols.decode(&new)
                         );
+                        self.log_reproducer(&derivation, base, |start, end| {
+                            weight.value(end) > weight.value(start)
+                        });
                         break;
                     }
                     gen_string = new;
-                    p_measure = new_p_measure;
+                    value = new_value;
                 } else {
                     break;
                 }
             }
         }
-        info!(
-            "{} system OK: Parikh measure decreases",
-            self.capitalize(&system)
-        )
-    }
 
-    fn start_m_invariant_tests(&mut self, base: bool) {
-        let system = if base {
-            "base".to_string()
-        } else {
-            "new".to_string()
-        };
-        info!(
-            "Testing {} system (strictly decreasing det = α^#c)...",
-            system
-        );
-        for _ in 0..self.tests_count {
-            let mut gen_string = self.gen_string();
-            let mut count_c = gen_string.chars().filter(|&ch| ch == 'c').count();
-            for _ in 0..self.max_rewrites {
-                if let Some(new) = self.random_rewrite(&gen_string, base) {
-                    let new_count_c = new.chars().filter(|&ch| ch == 'c').count();
-                    if new_count_c > count_c {
-                        error!(
-                            "Invariant fail: {gen_string} → {new} (#c {count_c} → {new_count_c})"
-                        );
-                        break;
-                    }
-                    gen_string = new;
-                    count_c = new_count_c;
-                } else {
-                    break;
-                }
+        match (report.breaking().is_empty(), failures) {
+            (true, 0) if report.is_strict() => {
+                info!("{} system OK: {measure} strictly decreases", self.capitalize(system))
             }
+            (true, 0) => info!("{} system OK: {measure} never increases", self.capitalize(system)),
+            (true, _) => error!(
+                "{failures} sampled derivations increase W although no rule does, the rewriting is broken"
+            ),
+            (false, 0) => warn!("Sampling found no derivation that increases W, the rules above do"),
+            (false, _) => error!(
+                "{} system fails: {failures} sampled derivations increase {measure}",
+                self.capitalize(system)
+            ),
         }
-        info!("{} system OK: det decreases", self.capitalize(&system));
+    }
+
+    fn start_weighted_parikh_measure_invariant_tests(&mut self, base: bool) {
+        self.start_weight_invariant_tests(base, "a=1 b=1 c=2", "Parikh measure");
+    }
+
+    fn start_m_invariant_tests(&mut self, base: bool) {
+        self.start_weight_invariant_tests(base, "c=1", "det = α^#c");
     }
 }
 
-pub fn start_meta_tests() {
-    let mut meta_tester = MetaTest::new();
+pub fn start_meta_tests(options: &Options) -> Result<(), String> {
+    let mut meta_tester = MetaTest::new(options)?;
 
     //start tests with M invariant
     meta_tester.start_m_invariant_tests(true);
@@ -258,4 +196,5 @@ pub fn start_meta_tests() {
     //start tests with Parikh measure invariant
     meta_tester.start_weighted_parikh_measure_invariant_tests(true);
     meta_tester.start_weighted_parikh_measure_invariant_tests(false);
+    Ok(())
 }
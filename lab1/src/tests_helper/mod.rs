@@ -1,2 +1,3 @@
 pub mod fuzz_tests;
-pub mod meta_tests;
\ No newline at end of file
+pub mod meta_tests;
+pub mod shrink;
\ No newline at end of file
@@ -0,0 +1,184 @@
+use crate::utils::srs::Srs;
+use crate::utils::word_problem::{Direction, EqualityProof, ProofStep};
+
+// a start word and the rewrites applied to it, as (rule, position) in the system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Derivation {
+    pub(crate) word: String,
+    pub(crate) steps: Vec<(usize, usize)>,
+}
+
+// letters of the start word are tagged (INPUT, index), letters written by step s are (s, index)
+// inside its right side; a step is anchored at the tag of the first letter it rewrites, so
+// it finds its place again after other letters or steps are dropped
+const INPUT: usize = usize::MAX;
+type Tag = (usize, usize);
+
+#[derive(Debug, Clone)]
+struct Step {
+    id: usize,
+    rule: usize,
+    anchor: Tag,
+}
+
+impl Derivation {
+    pub(crate) fn end(&self, srs: &Srs) -> String {
+        self.steps
+            .iter()
+            .fold(self.word.clone(), |word, &redex| srs.rewrite_redex(&word, redex))
+    }
+
+    pub(crate) fn proof(&self, srs: &Srs) -> EqualityProof {
+        let mut word = self.word.clone();
+        let mut steps = vec![];
+        for &(rule, position) in &self.steps {
+            word = srs.rewrite_redex(&word, (rule, position));
+            steps.push(ProofStep {
+                left: srs.left_rules[rule].clone(),
+                right: srs.right_rules[rule].clone(),
+                position,
+                direction: Direction::Forward,
+                word: word.clone(),
+            });
+        }
+        EqualityProof {
+            start: self.word.clone(),
+            steps,
+        }
+    }
+
+    fn tagged(&self, srs: &Srs) -> (Vec<(char, Tag)>, Vec<Step>) {
+        let start: Vec<(char, Tag)> = self.word.chars().enumerate().map(|(i, letter)| (letter, (INPUT, i))).collect();
+        let mut word = start.clone();
+        let mut steps = vec![];
+        for (id, &(rule, position)) in self.steps.iter().enumerate() {
+            steps.push(Step {
+                id,
+                rule,
+                anchor: word[position].1,
+            });
+            apply(srs, &mut word, id, rule, position);
+        }
+        (start, steps)
+    }
+}
+
+fn apply(srs: &Srs, word: &mut Vec<(char, Tag)>, id: usize, rule: usize, position: usize) {
+    let left = &srs.left_rules[rule];
+    let right: Vec<(char, Tag)> = srs.right_rules[rule].chars().enumerate().map(|(i, letter)| (letter, (id, i))).collect();
+    word.splice(position..position + left.len(), right);
+}
+
+// None if some step no longer finds its left side at its anchor
+fn replay(srs: &Srs, start: &[(char, Tag)], steps: &[Step]) -> Option<Derivation> {
+    let mut word = start.to_vec();
+    let mut redexes = vec![];
+    for step in steps {
+        let position = word.iter().position(|&(_, tag)| tag == step.anchor)?;
+        let left = &srs.left_rules[step.rule];
+        let found = word.get(position..position + left.len())?;
+        if !found.iter().map(|&(letter, _)| letter).eq(left.chars()) {
+            return None;
+        }
+        redexes.push((step.rule, position));
+        apply(srs, &mut word, step.id, step.rule, position);
+    }
+    Some(Derivation {
+        word: start.iter().map(|&(letter, _)| letter).collect(),
+        steps: redexes,
+    })
+}
+
+// one ddmin pass over items: drops chunks from half the length down to single items,
+// keeping every removal after which the case still fails
+fn drop_chunks<T: Clone>(items: &mut Vec<T>, mut still_fails: impl FnMut(&[T]) -> bool) -> bool {
+    let mut changed = false;
+    let mut size = items.len().div_ceil(2);
+    while size > 0 {
+        let mut start = 0;
+        while start < items.len() {
+            let mut candidate = items.clone();
+            candidate.drain(start..(start + size).min(items.len()));
+            if still_fails(&candidate) {
+                *items = candidate;
+                changed = true;
+            } else {
+                start += size;
+            }
+        }
+        size /= 2;
+    }
+    changed
+}
+
+// delta debugging: deletes letters of the start word and rewrite steps while
+// fails(start, end) holds, until no single letter or step can go
+pub(crate) fn shrink(srs: &Srs, derivation: &Derivation, mut fails: impl FnMut(&str, &str) -> bool) -> Derivation {
+    let (mut start, mut steps) = derivation.tagged(srs);
+    let mut check = |start: &[(char, Tag)], steps: &[Step]| {
+        replay(srs, start, steps).filter(|derivation| fails(&derivation.word, &derivation.end(srs)))
+    };
+    loop {
+        let steps_changed = drop_chunks(&mut steps, |candidate| check(&start, candidate).is_some());
+        let letters_changed = drop_chunks(&mut start, |candidate| check(candidate, &steps).is_some());
+        if !steps_changed && !letters_changed {
+            break;
+        }
+    }
+    replay(srs, &start, &steps).expect("every kept change replays")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::symbols::Symbols;
+
+    fn srs(rules: &str) -> Srs {
+        let letters = ["a", "b", "c"].iter().map(|letter| letter.to_string()).collect();
+        let mut srs = Srs {
+            symbols: Symbols::new(letters).unwrap(),
+            ..Srs::default()
+        };
+        srs.parse_rules("test", rules).unwrap();
+        srs
+    }
+
+    #[test]
+    fn shrinks_to_a_single_rewrite() {
+        let srs = srs("ab -> ba\n");
+        let derivation = Derivation {
+            word: "caabb".to_string(),
+            steps: vec![(0, 2), (0, 1), (0, 3)],
+        };
+        assert_eq!(derivation.end(&srs), "cbaba");
+
+        let shrunk = shrink(&srs, &derivation, |u, v| u != v);
+        assert_eq!(
+            shrunk,
+            Derivation {
+                word: "ab".to_string(),
+                steps: vec![(0, 0)],
+            }
+        );
+        assert_eq!(shrunk.proof(&srs).to_string(), "ab --(ab -> ba @0)--> ba");
+    }
+
+    #[test]
+    fn steps_keep_their_anchor_when_letters_go() {
+        let srs = srs("ab -> ba\nbb -> c\n");
+        let derivation = Derivation {
+            word: "abab".to_string(),
+            steps: vec![(0, 2), (1, 1)],
+        };
+        assert_eq!(derivation.end(&srs), "aca");
+
+        let shrunk = shrink(&srs, &derivation, |u, v| !u.contains('c') && v.contains('c'));
+        assert_eq!(
+            shrunk,
+            Derivation {
+                word: "bab".to_string(),
+                steps: vec![(0, 1), (1, 0)],
+            }
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::cli::{csv_writer, Format, Options};
+use crate::utils::knuth_bendix::{CriticalPair, CriticalPairKind};
+use crate::utils::loops::LoopWitness;
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::symbols::Symbols;
+use log::info;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub(crate) struct CriticalPairEntry {
+    pub(crate) pair: CriticalPair,
+    pub(crate) first_rule: (String, String),
+    pub(crate) second_rule: (String, String),
+    pub(crate) left_normal_forms: Vec<String>,
+    pub(crate) right_normal_forms: Vec<String>,
+    pub(crate) joinable: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConfluenceReport {
+    pub(crate) entries: Vec<CriticalPairEntry>,
+    // the alphabet of the system, to print words with letter names
+    pub(crate) symbols: Symbols,
+}
+
+impl ConfluenceReport {
+    pub(crate) fn is_locally_confluent(&self) -> bool {
+        self.entries.iter().all(|entry| entry.joinable)
+    }
+
+    pub(crate) fn failures(&self) -> impl Iterator<Item = &CriticalPairEntry> {
+        self.entries.iter().filter(|entry| !entry.joinable)
+    }
+}
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+fn show_forms(forms: &[String]) -> String {
+    forms.iter().map(|form| show(form)).collect::<Vec<_>>().join(", ")
+}
+
+impl fmt::Display for ConfluenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = [
+            "#", "kind", "rules", "word", "rewrite 1", "rewrite 2", "NF 1", "NF 2", "joins",
+        ];
+        let rows: Vec<[String; 9]> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                [
+                    (i + 1).to_string(),
+                    match entry.pair.kind {
+                        CriticalPairKind::Overlap => "overlap".to_string(),
+                        CriticalPairKind::Inclusion => "inclusion".to_string(),
+                    },
+                    format!(
+                        "{} / {}",
+                        RulesAddition::rule_line(&entry.first_rule.0, &entry.first_rule.1),
+                        RulesAddition::rule_line(&entry.second_rule.0, &entry.second_rule.1)
+                    ),
+                    show(&entry.pair.word).to_string(),
+                    show(&entry.pair.left).to_string(),
+                    show(&entry.pair.right).to_string(),
+                    show_forms(&entry.left_normal_forms),
+                    show_forms(&entry.right_normal_forms),
+                    if entry.joinable { "yes" } else { "NO" }.to_string(),
+                ]
+                .map(|cell| self.symbols.decode(&cell))
+            })
+            .collect();
+
+        let mut widths = header.map(|title| title.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let write_row = |f: &mut fmt::Formatter<'_>, cells: &[&str]| -> fmt::Result {
+            for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+                if i > 0 {
+                    write!(f, " | ")?;
+                }
+                write!(f, "{cell}{}", " ".repeat(width - cell.chars().count()))?;
+            }
+            writeln!(f)
+        };
+
+        write_row(f, &header)?;
+        let separator: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+        writeln!(f, "{}", separator.join("-|-"))?;
+        for row in &rows {
+            let cells: Vec<&str> = row.iter().map(String::as_str).collect();
+            write_row(f, &cells)?;
+        }
+
+        let failed = self.failures().count();
+        if self.is_locally_confluent() {
+            writeln!(
+                f,
+                "All {} critical pairs join: the system is locally confluent",
+                self.entries.len()
+            )
+        } else {
+            writeln!(
+                f,
+                "{failed} of {} critical pairs do not join: the system is not locally confluent",
+                self.entries.len()
+            )
+        }
+    }
+}
+
+fn write_report(report: &ConfluenceReport) -> csv::Result<()> {
+    let mut writer = csv_writer();
+    writer.write_record([
+        "kind", "first_rule", "second_rule", "word", "position", "rewrite_1", "rewrite_2", "nf_1", "nf_2", "joins",
+    ])?;
+    let decode = |text: &str| report.symbols.decode(text);
+    for entry in &report.entries {
+        writer.write_record([
+            match entry.pair.kind {
+                CriticalPairKind::Overlap => "overlap",
+                CriticalPairKind::Inclusion => "inclusion",
+            },
+            &decode(&RulesAddition::rule_line(&entry.first_rule.0, &entry.first_rule.1)),
+            &decode(&RulesAddition::rule_line(&entry.second_rule.0, &entry.second_rule.1)),
+            &decode(&entry.pair.word),
+            &entry.pair.position.to_string(),
+            &decode(&entry.pair.left),
+            &decode(&entry.pair.right),
+            &decode(&entry.left_normal_forms.join(",")),
+            &decode(&entry.right_normal_forms.join(",")),
+            if entry.joinable { "yes" } else { "no" },
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+impl RulesAddition {
+    pub(crate) fn confluence_report(&mut self) -> Result<ConfluenceReport, LoopWitness> {
+        let mut entries = vec![];
+        for pair in self.critical_pairs() {
+            let mut left_normal_forms = self.get_normal_forms(&pair.left, vec![], &mut HashMap::new())?;
+            let mut right_normal_forms = self.get_normal_forms(&pair.right, vec![], &mut HashMap::new())?;
+            left_normal_forms.sort_by(|a, b| self.compare_shortlex(a, b));
+            right_normal_forms.sort_by(|a, b| self.compare_shortlex(a, b));
+            let joinable = left_normal_forms
+                .iter()
+                .any(|form| right_normal_forms.contains(form));
+
+            entries.push(CriticalPairEntry {
+                first_rule: (
+                    self.srs.left_rules[pair.first_rule].clone(),
+                    self.srs.right_rules[pair.first_rule].clone(),
+                ),
+                second_rule: (
+                    self.srs.left_rules[pair.second_rule].clone(),
+                    self.srs.right_rules[pair.second_rule].clone(),
+                ),
+                pair,
+                left_normal_forms,
+                right_normal_forms,
+                joinable,
+            });
+        }
+        Ok(ConfluenceReport {
+            entries,
+            symbols: self.srs.symbols.clone(),
+        })
+    }
+}
+
+pub fn start_confluence_report(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    rules_addition.read_rules(&options.rules_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    }
+    info!("Rules read");
+
+    match rules_addition.confluence_report() {
+        Ok(report) if options.format == Format::Csv => {
+            if let Err(e) = write_report(&report) {
+                return Err(format!("Error while writing csv {e}"));
+            }
+        }
+        Ok(report) => print!("{report}"),
+        Err(witness) => {
+            return Err(format!("Rewriting does not terminate: {}", rules_addition.decode(&witness.to_string())));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    fn summary(pair: &CriticalPair) -> (CriticalPairKind, usize, &str, &str, &str) {
+        (pair.kind, pair.position, &pair.word, &pair.left, &pair.right)
+    }
+
+    #[test]
+    fn overlaps_and_inclusions_are_found() {
+        let rules_addition = system(&["a", "b"], "aba -> b\nb -> a\n");
+        let pairs = rules_addition.critical_pairs();
+        assert_eq!(
+            pairs.iter().map(summary).collect::<Vec<_>>(),
+            [
+                (CriticalPairKind::Overlap, 2, "ababa", "bba", "abb"),
+                (CriticalPairKind::Inclusion, 1, "aba", "b", "aaa"),
+            ]
+        );
+    }
+
+    #[test]
+    fn report_tells_joinable_pairs_apart() {
+        let mut rules_addition = system(&["a", "b"], "ba -> ab\nbb -> a\n");
+        let report = rules_addition.confluence_report().unwrap();
+        assert_eq!(report.entries.len(), 2);
+        assert!(report.is_locally_confluent());
+
+        let mut rules_addition = system(&["a", "b"], "ab -> a\nba -> b\n");
+        let report = rules_addition.confluence_report().unwrap();
+        let failures: Vec<_> = report
+            .failures()
+            .map(|entry| (show_forms(&entry.left_normal_forms), show_forms(&entry.right_normal_forms)))
+            .collect();
+        assert_eq!(failures, [("aa".into(), "a".into()), ("bb".into(), "b".into())]);
+    }
+}
@@ -0,0 +1,301 @@
+use crate::cli::{csv_writer, Format, Options};
+use crate::utils::knuth_bendix::StopReason;
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::symbols::Symbols;
+use crate::utils::word_problem::EqualityProof;
+use log::info;
+use std::fmt;
+
+type Rule = (String, String);
+
+#[derive(Debug, Clone)]
+pub(crate) enum Reduction {
+    // the left side contains the left side of `by`, and both sides rewrite to normal_form
+    Removed {
+        rule: Rule,
+        by: Rule,
+        position: usize,
+        normal_form: String,
+    },
+    // the left side contains the left side of `by`, but the sides have different normal forms
+    Replaced {
+        rule: Rule,
+        by: Rule,
+        position: usize,
+        new_rule: Rule,
+    },
+    // like Replaced, but the ordering cannot orient the normal forms, so they stay an equation
+    Unoriented {
+        rule: Rule,
+        by: Rule,
+        position: usize,
+        equation: Rule,
+    },
+    // the right side was not in normal form
+    RightSide {
+        rule: Rule,
+        proof: EqualityProof,
+    },
+}
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+fn line(rule: &Rule) -> String {
+    RulesAddition::rule_line(&rule.0, &rule.1)
+}
+
+impl Reduction {
+    pub(crate) fn action(&self) -> &'static str {
+        match self {
+            Reduction::Removed { .. } => "removed",
+            Reduction::Replaced { .. } => "replaced",
+            Reduction::Unoriented { .. } => "unoriented",
+            Reduction::RightSide { .. } => "rewritten",
+        }
+    }
+
+    pub(crate) fn rule(&self) -> &Rule {
+        match self {
+            Reduction::Removed { rule, .. }
+            | Reduction::Replaced { rule, .. }
+            | Reduction::Unoriented { rule, .. }
+            | Reduction::RightSide { rule, .. } => rule,
+        }
+    }
+
+    pub(crate) fn result(&self) -> Option<Rule> {
+        match self {
+            Reduction::Removed { .. } => None,
+            Reduction::Replaced { new_rule, .. } => Some(new_rule.clone()),
+            Reduction::Unoriented { equation, .. } => Some(equation.clone()),
+            Reduction::RightSide { rule, proof } => Some((rule.0.clone(), proof.steps.last()?.word.clone())),
+        }
+    }
+
+    pub(crate) fn result_line(&self) -> Option<String> {
+        match self {
+            Reduction::Unoriented { equation, .. } => Some(RulesAddition::equation_line(&equation.0, &equation.1)),
+            _ => self.result().map(|rule| line(&rule)),
+        }
+    }
+
+    pub(crate) fn justification(&self) -> String {
+        match self {
+            Reduction::Removed {
+                by,
+                position,
+                normal_form,
+                ..
+            } => format!(
+                "left side contains {} at {position}, both sides reduce to {}",
+                line(by),
+                show(normal_form)
+            ),
+            Reduction::Replaced { by, position, .. } => format!(
+                "left side contains {} at {position}, normal forms of the sides differ",
+                line(by)
+            ),
+            Reduction::Unoriented { by, position, .. } => format!(
+                "left side contains {} at {position}, the ordering cannot orient the normal forms of the sides",
+                line(by)
+            ),
+            Reduction::RightSide { proof, .. } => proof.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Reduction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.action(), line(self.rule()))?;
+        if let Some(result) = self.result_line() {
+            write!(f, " to {result}")?;
+        }
+        write!(f, ": {}", self.justification())
+    }
+}
+
+impl RulesAddition {
+    // (rule, rule whose left side occurs in it, position); of two rules with the same
+    // left side the one with the greater right side goes, or the later one on a tie
+    fn reducible_left_side(&self) -> Option<(usize, usize, usize)> {
+        for (i, left) in self.srs.left_rules.iter().enumerate() {
+            for (j, other) in self.srs.left_rules.iter().enumerate() {
+                if i == j || other.is_empty() {
+                    continue;
+                }
+                if left == other {
+                    let (right, other_right) = (&self.srs.right_rules[i], &self.srs.right_rules[j]);
+                    let removable = self.ordering.greater(right, other_right)
+                        || (!self.ordering.greater(other_right, right) && j < i);
+                    if !removable {
+                        continue;
+                    }
+                }
+                if let Some(position) = left.find(other.as_str()) {
+                    return Some((i, j, position));
+                }
+            }
+        }
+        None
+    }
+
+    // turns the rules into the reduced system: no left side contains another one
+    // and every right side is irreducible
+    pub(crate) fn interreduce(&mut self, max_steps: usize) -> Result<Vec<Reduction>, StopReason> {
+        let mut log = vec![];
+        let mut steps = 0;
+
+        while let Some((i, j, position)) = self.reducible_left_side() {
+            let by = (self.srs.left_rules[j].clone(), self.srs.right_rules[j].clone());
+            let rule = (self.srs.left_rules.remove(i), self.srs.right_rules.remove(i));
+            let left = self
+                .normalize_counted(&rule.0, &mut steps, max_steps)
+                .ok_or(StopReason::StepBudgetExhausted(steps))?;
+            let right = self
+                .normalize_counted(&rule.1, &mut steps, max_steps)
+                .ok_or(StopReason::StepBudgetExhausted(steps))?;
+
+            let reduction = if left == right {
+                Reduction::Removed {
+                    rule,
+                    by,
+                    position,
+                    normal_form: left,
+                }
+            } else if let Some(new_rule) = self.orient(&left, &right) {
+                self.srs.left_rules.push(new_rule.0.clone());
+                self.srs.right_rules.push(new_rule.1.clone());
+                Reduction::Replaced {
+                    rule,
+                    by,
+                    position,
+                    new_rule,
+                }
+            } else {
+                self.add_equation(&left, &right);
+                Reduction::Unoriented {
+                    rule,
+                    by,
+                    position,
+                    equation: (left, right),
+                }
+            };
+            info!("{}", self.decode(&reduction.to_string()));
+            log.push(reduction);
+        }
+
+        // left sides are fixed from here on, so one pass leaves every right side irreducible
+        for i in 0..self.srs.right_rules.len() {
+            let right = self.srs.right_rules[i].clone();
+            let (normal_form, proof_steps) = self
+                .normalize_with_steps_counted(&right, &mut steps, max_steps)
+                .ok_or(StopReason::StepBudgetExhausted(steps))?;
+            if normal_form == right {
+                continue;
+            }
+            let reduction = Reduction::RightSide {
+                rule: (self.srs.left_rules[i].clone(), right.clone()),
+                proof: EqualityProof {
+                    start: right,
+                    steps: proof_steps,
+                },
+            };
+            info!("{}", self.decode(&reduction.to_string()));
+            log.push(reduction);
+            self.srs.right_rules[i] = normal_form;
+        }
+        Ok(log)
+    }
+}
+
+fn write_log(log: &[Reduction], symbols: &Symbols) -> csv::Result<()> {
+    let mut writer = csv_writer();
+    writer.write_record(["action", "rule", "result", "justification"])?;
+    for reduction in log {
+        writer.write_record([
+            reduction.action().to_string(),
+            symbols.decode(&line(reduction.rule())),
+            reduction.result_line().map(|line| symbols.decode(&line)).unwrap_or_default(),
+            symbols.decode(&reduction.justification()),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn start_interreduction(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    let Some(rules) = rules_addition.read_raw_rules(&options.rules_path) else {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    };
+    rules_addition.set_rules(rules);
+    info!("Rules read");
+
+    let log = match rules_addition.interreduce(options.completion.max_steps) {
+        Ok(log) => log,
+        Err(reason) => {
+            return Err(format!("Interreduction stopped: {}", rules_addition.decode(&reason.to_string())));
+        }
+    };
+
+    match options.format {
+        Format::Csv => {
+            if let Err(e) = write_log(&log, &rules_addition.srs.symbols) {
+                return Err(format!("Error while writing csv {e}"));
+            }
+        }
+        Format::Text => {
+            for reduction in &log {
+                println!("{}", rules_addition.decode(&reduction.to_string()));
+            }
+            println!("Reduced system ({} rules):", rules_addition.srs.left_rules.len());
+            for (left, right) in rules_addition.srs.left_rules.iter().zip(&rules_addition.srs.right_rules) {
+                println!("{}", rules_addition.decode(&RulesAddition::rule_line(left, right)));
+            }
+            for (u, v) in &rules_addition.srs.equations {
+                println!("{}", rules_addition.decode(&RulesAddition::equation_line(u, v)));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn right_sides_are_normalized_with_a_proof() {
+        let mut rules_addition = system(&["a", "b", "c"], "c -> ab\nab -> b\n");
+        let log = rules_addition.interreduce(100).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].result(), Some(("c".to_string(), "b".to_string())));
+        assert_eq!(log[0].justification(), "ab --(ab -> b @0)--> b");
+    }
+
+    #[test]
+    fn right_side_pass_keeps_to_the_budget() {
+        let mut rules_addition = system(&["a", "b"], "a -> b\nb -> a\n");
+        assert!(matches!(
+            rules_addition.interreduce(50),
+            Err(StopReason::StepBudgetExhausted(50))
+        ));
+    }
+
+    #[test]
+    fn unorientable_sides_are_kept_as_an_equation() {
+        let mut rules_addition = system(&["a", "b", "c"], "ordering: weight weights a=1 b=1 c=1\naa -> b\naac -> bb\n");
+        let log = rules_addition.interreduce(100).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].to_string(), "unoriented aac -> bb to bc = bb: left side contains aa -> b at 0, the ordering cannot orient the normal forms of the sides");
+        assert_eq!(rules_addition.srs.left_rules, ["aa"]);
+        assert_eq!(rules_addition.equation_lines().as_deref(), Some("bc = bb"));
+    }
+}
@@ -0,0 +1,686 @@
+use crate::cli::{csv_writer, Format, Options};
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::srs::Srs;
+use crate::utils::symbols::Symbols;
+use log::{info, warn};
+use std::fmt;
+
+type Rule = (String, String);
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+// W(w) = the sum of the weights of the letters of w, letters without a weight count 0;
+// shown with symbols, like words
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LinearWeight {
+    pub(crate) weights: Vec<(char, i64)>,
+}
+
+impl LinearWeight {
+    // `a=1 b=1 c=2`
+    pub(crate) fn parse(text: &str, symbols: &Symbols) -> Result<Self, String> {
+        let mut weights: Vec<(char, i64)> = vec![];
+        for token in text.split_whitespace() {
+            let Some((letter, value)) = token.split_once('=') else {
+                return Err(format!("expected `letter=number`, got `{token}`"));
+            };
+            let value = value
+                .parse::<i64>()
+                .map_err(|e| format!("bad number in `{token}`: {e}"))?;
+            let Ok(symbol) = symbols.symbol(letter) else {
+                warn!("Letter `{letter}` is not in the alphabet, its count is always 0");
+                continue;
+            };
+            if symbols.rank(symbol).is_none() {
+                warn!("Letter `{letter}` is not in the alphabet, its count is always 0");
+            }
+            if weights.iter().any(|&(known, _)| known == symbol) {
+                return Err(format!("letter `{letter}` has two weights"));
+            }
+            weights.push((symbol, value));
+        }
+        Ok(Self { weights })
+    }
+
+    pub(crate) fn value(&self, word: &str) -> i64 {
+        word.chars()
+            .map(|letter| {
+                self.weights
+                    .iter()
+                    .find(|&&(known, _)| known == letter)
+                    .map_or(0, |&(_, value)| value)
+            })
+            .sum()
+    }
+}
+
+impl fmt::Display for LinearWeight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<(char, i64)> = self.weights.iter().copied().filter(|&(_, value)| value != 0).collect();
+        if terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (i, &(letter, value)) in terms.iter().enumerate() {
+            let sign = match (i, value < 0) {
+                (0, true) => "-",
+                (0, false) => "",
+                (_, true) => " - ",
+                (_, false) => " + ",
+            };
+            match value.abs() {
+                1 => write!(f, "{sign}#{letter}")?,
+                factor => write!(f, "{sign}{factor}*#{letter}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Change {
+    Decreases,
+    Keeps,
+    Increases,
+}
+
+impl Change {
+    fn name(self) -> &'static str {
+        match self {
+            Change::Decreases => "decreases",
+            Change::Keeps => "keeps",
+            Change::Increases => "increases",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct InvariantReport {
+    pub(crate) weight: LinearWeight,
+    // every rule with W of its left and right side
+    pub(crate) rules: Vec<(Rule, i64, i64)>,
+}
+
+impl InvariantReport {
+    pub(crate) fn change(&self, i: usize) -> Change {
+        let (_, left, right) = &self.rules[i];
+        match right.cmp(left) {
+            std::cmp::Ordering::Less => Change::Decreases,
+            std::cmp::Ordering::Equal => Change::Keeps,
+            std::cmp::Ordering::Greater => Change::Increases,
+        }
+    }
+
+    // indexes of the rules that increase W
+    pub(crate) fn breaking(&self) -> Vec<usize> {
+        (0..self.rules.len())
+            .filter(|&i| self.change(i) == Change::Increases)
+            .collect()
+    }
+
+    pub(crate) fn is_strict(&self) -> bool {
+        (0..self.rules.len()).all(|i| self.change(i) == Change::Decreases)
+    }
+
+    pub(crate) fn verdict(&self) -> String {
+        let breaking = self.breaking();
+        if !breaking.is_empty() {
+            return format!("W is not an invariant, {} of {} rules increase it", breaking.len(), self.rules.len());
+        }
+        if self.is_strict() {
+            return "W strictly decreases with every rewrite".to_string();
+        }
+        let kept = (0..self.rules.len()).filter(|&i| self.change(i) == Change::Keeps).count();
+        format!("W never increases, {kept} of {} rules keep it, so the decrease is only weak", self.rules.len())
+    }
+}
+
+impl fmt::Display for InvariantReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "W(w) = {}", self.weight)?;
+        for (i, ((left, right), w_left, w_right)) in self.rules.iter().enumerate() {
+            writeln!(
+                f,
+                "{}: {w_left} -> {w_right}, {}",
+                RulesAddition::rule_line(left, right),
+                self.change(i).name()
+            )?;
+        }
+        writeln!(f, "{}", self.verdict())
+    }
+}
+
+impl Srs {
+    // W is additive, so rewriting x l y to x r y changes W by exactly W(r) - W(l): checking the
+    // rules one by one decides the invariant for every word and every derivation; equations
+    // are applied in both directions, so each one is checked both ways
+    pub(crate) fn verify_invariant(&self, weight: &LinearWeight) -> InvariantReport {
+        let equations = self
+            .equations
+            .iter()
+            .flat_map(|(u, v)| [(u.clone(), v.clone()), (v.clone(), u.clone())]);
+        InvariantReport {
+            weight: weight.clone(),
+            rules: self
+                .rules()
+                .into_iter()
+                .chain(equations)
+                .map(|rule| {
+                    let (left, right) = (weight.value(&rule.0), weight.value(&rule.1));
+                    (rule, left, right)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct InvariantLimits {
+    pub(crate) max_modulus: i64,
+    pub(crate) max_points: usize,
+    pub(crate) max_candidates: usize,
+}
+
+impl Default for InvariantLimits {
+    fn default() -> Self {
+        Self {
+            max_modulus: 6,
+            max_points: 3,
+            max_candidates: 1_000_000,
+        }
+    }
+}
+
+// a quantity every rule keeps, so congruent words share its value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Invariant {
+    Linear(LinearWeight),
+    Modular { weight: LinearWeight, modulus: i64 },
+    // letters act on the points 0..points by these maps, a word by their composition left to right,
+    // a homomorphism into the full transformation monoid
+    Transformation { points: usize, maps: Vec<(char, Vec<usize>)> },
+}
+
+fn act(maps: &[(char, Vec<usize>)], points: usize, word: &str) -> Vec<usize> {
+    (0..points)
+        .map(|point| {
+            word.chars().fold(point, |point, letter| {
+                maps.iter()
+                    .find(|(known, _)| *known == letter)
+                    .map_or(point, |(_, map)| map[point])
+            })
+        })
+        .collect()
+}
+
+fn show_map(map: &[usize]) -> String {
+    let images: Vec<String> = map.iter().map(usize::to_string).collect();
+    format!("[{}]", images.join(" "))
+}
+
+impl Invariant {
+    pub(crate) fn value(&self, word: &str) -> String {
+        match self {
+            Invariant::Linear(weight) => weight.value(word).to_string(),
+            Invariant::Modular { weight, modulus } => weight.value(word).rem_euclid(*modulus).to_string(),
+            Invariant::Transformation { points, maps } => show_map(&act(maps, *points, word)),
+        }
+    }
+
+    pub(crate) fn kept_by(&self, rules: &[Rule]) -> bool {
+        rules.iter().all(|(left, right)| self.value(left) == self.value(right))
+    }
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Invariant::Linear(weight) => write!(f, "W(w) = {weight}"),
+            Invariant::Modular { weight, modulus } => write!(f, "W(w) = {weight} mod {modulus}"),
+            Invariant::Transformation { points, maps } => {
+                let maps: Vec<String> = maps
+                    .iter()
+                    .map(|(letter, map)| format!("{letter} ↦ {}", show_map(map)))
+                    .collect();
+                write!(f, "the action {} on {points} points", maps.join(", "))
+            }
+        }
+    }
+}
+
+// a certificate that u and v are not congruent, shown with symbols like words
+#[derive(Debug, Clone)]
+pub(crate) struct Separation {
+    pub(crate) invariant: Invariant,
+    pub(crate) u: String,
+    pub(crate) v: String,
+}
+
+impl fmt::Display for Separation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is kept by every rule but is {} on {} and {} on {}",
+            self.invariant,
+            self.invariant.value(&self.u),
+            show(&self.u),
+            self.invariant.value(&self.v),
+            show(&self.v)
+        )
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+// divides by the gcd and makes the first non-zero entry positive
+fn primitive(vector: &mut [i64]) {
+    let divisor = vector.iter().fold(0, |divisor, &x| gcd(divisor, x));
+    let sign = vector.iter().find(|&&x| x != 0).map_or(1, |x| x.signum());
+    if divisor > 0 {
+        vector.iter_mut().for_each(|x| *x = *x / divisor * sign);
+    }
+}
+
+// a basis of the integer vectors w with row · w = 0 for every row
+fn integer_kernel(mut rows: Vec<Vec<i64>>, columns: usize) -> Vec<Vec<i64>> {
+    let mut pivots: Vec<usize> = vec![];
+    for column in 0..columns {
+        let rank = pivots.len();
+        let Some(row) = (rank..rows.len()).find(|&row| rows[row][column] != 0) else {
+            continue;
+        };
+        rows.swap(rank, row);
+        for other in 0..rows.len() {
+            if other != rank && rows[other][column] != 0 {
+                let (a, b) = (rows[rank][column], rows[other][column]);
+                let pivot_row = rows[rank].clone();
+                for (x, pivot) in rows[other].iter_mut().zip(pivot_row) {
+                    *x = a * *x - b * pivot;
+                }
+                primitive(&mut rows[other]);
+            }
+        }
+        pivots.push(column);
+    }
+
+    // every row now reads pivot * x_pivot + sum of free columns = 0
+    let scale = pivots
+        .iter()
+        .enumerate()
+        .fold(1, |scale, (row, &column)| scale / gcd(scale, rows[row][column]) * rows[row][column].abs());
+    (0..columns)
+        .filter(|column| !pivots.contains(column))
+        .map(|free| {
+            let mut vector = vec![0; columns];
+            vector[free] = scale;
+            for (row, &column) in pivots.iter().enumerate() {
+                vector[column] = -rows[row][free] * (scale / rows[row][column]);
+            }
+            primitive(&mut vector);
+            vector
+        })
+        .collect()
+}
+
+// the letters given maps in order, the rules checked once the letter at each depth has its map
+struct MapSearch {
+    letters: Vec<char>,
+    checked_at: Vec<Vec<usize>>,
+    points: usize,
+    all_maps: Vec<Vec<usize>>,
+}
+
+struct Miner<'a> {
+    rules: &'a [Rule],
+    letters: Vec<char>,
+    limits: &'a InvariantLimits,
+    candidates: usize,
+}
+
+impl Miner<'_> {
+    fn spend(&mut self) -> bool {
+        self.candidates += 1;
+        self.candidates <= self.limits.max_candidates
+    }
+
+    fn parikh(&self, word: &str) -> Vec<i64> {
+        self.letters
+            .iter()
+            .map(|&letter| word.chars().filter(|&x| x == letter).count() as i64)
+            .collect()
+    }
+
+    fn weight(&self, values: &[i64]) -> LinearWeight {
+        LinearWeight {
+            weights: self.letters.iter().copied().zip(values.iter().copied()).collect(),
+        }
+    }
+
+    fn differences(&self) -> Vec<Vec<i64>> {
+        self.rules
+            .iter()
+            .map(|(left, right)| {
+                let (left, right) = (self.parikh(left), self.parikh(right));
+                left.iter().zip(&right).map(|(l, r)| l - r).collect()
+            })
+            .collect()
+    }
+
+    fn linear(&self) -> Vec<LinearWeight> {
+        integer_kernel(self.differences(), self.letters.len())
+            .iter()
+            .map(|values| self.weight(values))
+            .collect()
+    }
+
+    // a linear invariant that separates u and v exists iff a basis vector does
+    fn separating_linear(&self, u: &str, v: &str) -> Option<Invariant> {
+        self.linear()
+            .into_iter()
+            .find(|weight| weight.value(u) != weight.value(v))
+            .map(Invariant::Linear)
+    }
+
+    // the reduction of a linear invariant separates only when the invariant itself does,
+    // so anything found here is new
+    fn separating_modular(&mut self, u: &str, v: &str) -> Option<Invariant> {
+        let differences = self.differences();
+        let target: Vec<i64> = self.parikh(u).iter().zip(self.parikh(v)).map(|(a, b)| a - b).collect();
+        let dot = |values: &[i64], vector: &[i64]| -> i64 { values.iter().zip(vector).map(|(a, b)| a * b).sum() };
+        for modulus in 2..=self.limits.max_modulus {
+            let mut values = vec![0; self.letters.len()];
+            while let Some(i) = values.iter().position(|&value| value < modulus - 1) {
+                values[i] += 1;
+                values[..i].iter_mut().for_each(|value| *value = 0);
+                if !self.spend() {
+                    return None;
+                }
+                let kept = differences.iter().all(|row| dot(&values, row) % modulus == 0);
+                if kept && dot(&values, &target) % modulus != 0 {
+                    return Some(Invariant::Modular {
+                        weight: self.weight(&values),
+                        modulus,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    // backtracking over the maps of the letters in order, a rule is checked
+    // as soon as its last letter has a map
+    fn separating_transformation(&mut self, u: &str, v: &str) -> Option<Invariant> {
+        let letters: Vec<char> = self
+            .letters
+            .iter()
+            .copied()
+            .filter(|&letter| {
+                self.rules.iter().any(|(left, right)| left.contains(letter) || right.contains(letter))
+                    || u.contains(letter)
+                    || v.contains(letter)
+            })
+            .collect();
+        let checked_at: Vec<Vec<usize>> = (0..letters.len())
+            .map(|depth| {
+                (0..self.rules.len())
+                    .filter(|&rule| {
+                        let (left, right) = &self.rules[rule];
+                        let last = left
+                            .chars()
+                            .chain(right.chars())
+                            .filter_map(|letter| letters.iter().position(|&x| x == letter))
+                            .max();
+                        last.map_or(depth == 0, |last| last == depth)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for points in 2..=self.limits.max_points {
+            let search = MapSearch {
+                letters: letters.clone(),
+                checked_at: checked_at.clone(),
+                points,
+                all_maps: (0..points.pow(points as u32))
+                    .map(|code| (0..points).map(|point| code / points.pow(point as u32) % points).collect())
+                    .collect(),
+            };
+            if let Some(invariant) = self.extend(&search, &mut vec![], u, v) {
+                return Some(invariant);
+            }
+            if self.candidates > self.limits.max_candidates {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn extend(
+        &mut self,
+        search: &MapSearch,
+        maps: &mut Vec<(char, Vec<usize>)>,
+        u: &str,
+        v: &str,
+    ) -> Option<Invariant> {
+        let (depth, points) = (maps.len(), search.points);
+        if depth == search.letters.len() {
+            return (act(maps, points, u) != act(maps, points, v)).then(|| Invariant::Transformation {
+                points,
+                maps: maps.clone(),
+            });
+        }
+        for map in &search.all_maps {
+            if !self.spend() {
+                return None;
+            }
+            maps.push((search.letters[depth], map.clone()));
+            let kept = search.checked_at[depth].iter().all(|&rule| {
+                let (left, right) = &self.rules[rule];
+                act(maps, points, left) == act(maps, points, right)
+            });
+            if kept && let Some(invariant) = self.extend(search, maps, u, v) {
+                return Some(invariant);
+            }
+            maps.pop();
+        }
+        None
+    }
+}
+
+// a basis of the linear invariants, every other one is a rational combination of these
+pub(crate) fn linear_invariants(rules: &[Rule], letters: &[char]) -> Vec<LinearWeight> {
+    let limits = InvariantLimits::default();
+    let miner = Miner {
+        rules,
+        letters: letters.to_vec(),
+        limits: &limits,
+        candidates: 0,
+    };
+    miner.linear()
+}
+
+// linear invariants first, then the same combinations mod k, then homomorphisms into
+// transformations of a few points; the first one that tells u and v apart is the certificate
+pub(crate) fn separate(rules: &[Rule], letters: &[char], u: &str, v: &str, limits: &InvariantLimits) -> Option<Separation> {
+    let mut miner = Miner {
+        rules,
+        letters: letters.to_vec(),
+        limits,
+        candidates: 0,
+    };
+    let invariant = miner
+        .separating_linear(u, v)
+        .or_else(|| miner.separating_modular(u, v))
+        .or_else(|| miner.separating_transformation(u, v))?;
+    debug_assert!(invariant.kept_by(rules));
+    Some(Separation {
+        invariant,
+        u: u.to_string(),
+        v: v.to_string(),
+    })
+}
+
+fn write_report(report: &InvariantReport, symbols: &Symbols) -> csv::Result<()> {
+    let mut writer = csv_writer();
+    writer.write_record(["rule", "w_left", "w_right", "change"])?;
+    for (i, ((left, right), w_left, w_right)) in report.rules.iter().enumerate() {
+        writer.write_record([
+            symbols.decode(&RulesAddition::rule_line(left, right)),
+            w_left.to_string(),
+            w_right.to_string(),
+            report.change(i).name().to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn start_invariant_check(weights: &str, options: &Options) -> Result<(), String> {
+    let srs = Srs::load(&options.rules_path, &options.alphabet_path)?;
+    let weight = LinearWeight::parse(weights, &srs.symbols)?;
+    info!(
+        "Checking W(w) = {} against {} rules",
+        srs.symbols.decode(&weight.to_string()),
+        srs.left_rules.len()
+    );
+
+    let report = srs.verify_invariant(&weight);
+    match options.format {
+        Format::Csv => {
+            if let Err(e) = write_report(&report, &srs.symbols) {
+                return Err(format!("Error while writing csv {e}"));
+            }
+        }
+        Format::Text => print!("{}", srs.symbols.decode(&report.to_string())),
+    }
+    Ok(())
+}
+
+pub fn start_separation(u: &str, v: &str, options: &Options) -> Result<(), String> {
+    let srs = Srs::load(&options.rules_path, &options.alphabet_path)?;
+    let (u, v) = (srs.encode(u)?, srs.encode(v)?);
+    let mut rules = srs.rules();
+    rules.extend(srs.equations.iter().cloned());
+    let letters = srs.symbols.alphabet();
+
+    let basis = linear_invariants(&rules, letters);
+    info!("{} independent linear invariants", basis.len());
+    let separation = separate(&rules, letters, &u, &v, &InvariantLimits::default());
+    match options.format {
+        Format::Text => {
+            let mut text = String::new();
+            for weight in &basis {
+                text += &format!("Linear invariant W(w) = {weight}\n");
+            }
+            text += &match separation {
+                Some(separation) => format!("{} != {}: {separation}", show(&u), show(&v)),
+                None => format!("No invariant found that tells {} and {} apart", show(&u), show(&v)),
+            };
+            println!("{}", srs.symbols.decode(&text));
+        }
+        Format::Csv => {
+            let (invariant, u_value, v_value) = match &separation {
+                Some(separation) => (
+                    srs.symbols.decode(&separation.invariant.to_string()),
+                    separation.invariant.value(&u),
+                    separation.invariant.value(&v),
+                ),
+                None => Default::default(),
+            };
+            let mut writer = csv_writer();
+            let written = writer
+                .write_record(["u", "v", "invariant", "u_value", "v_value"])
+                .and_then(|_| {
+                    writer.write_record([srs.symbols.decode(&u), srs.symbols.decode(&v), invariant, u_value, v_value])
+                })
+                .and_then(|_| writer.flush().map_err(csv::Error::from));
+            if let Err(e) = written {
+                return Err(format!("Error while writing csv {e}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    fn rules(rules: &[(&str, &str)]) -> Vec<Rule> {
+        rules.iter().map(|&(left, right)| (left.to_string(), right.to_string())).collect()
+    }
+
+    #[test]
+    fn integer_kernel_is_primitive_and_integral() {
+        assert_eq!(integer_kernel(vec![vec![2, -3]], 2), [[3, 2]]);
+        assert_eq!(integer_kernel(vec![vec![1, -1, 0]], 3), [[1, 1, 0], [0, 0, 1]]);
+        assert_eq!(integer_kernel(vec![vec![2, 0], vec![0, 1]], 2), Vec::<Vec<i64>>::new());
+
+        let rows = vec![vec![2, 4, -6, 0], vec![1, 0, 1, -3]];
+        let kernel = integer_kernel(rows.clone(), 4);
+        assert_eq!(kernel.len(), 2);
+        for vector in &kernel {
+            for row in &rows {
+                assert_eq!(row.iter().zip(vector).map(|(x, w)| x * w).sum::<i64>(), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn linear_modular_and_transformation_invariants_separate_in_turn() {
+        let letters = ['a', 'b'];
+        let limits = InvariantLimits::default();
+
+        let commuting = rules(&[("ba", "ab")]);
+        let separation = separate(&commuting, &letters, "aab", "abb", &limits).unwrap();
+        assert!(matches!(separation.invariant, Invariant::Linear(_)));
+
+        let involution = rules(&[("aa", "")]);
+        let separation = separate(&involution, &letters, "a", "", &limits).unwrap();
+        assert!(matches!(separation.invariant, Invariant::Modular { modulus: 2, .. }));
+
+        let idempotents = rules(&[("aa", "a"), ("bb", "b")]);
+        let separation = separate(&idempotents, &letters, "ab", "ba", &limits).unwrap();
+        assert!(matches!(separation.invariant, Invariant::Transformation { .. }));
+        assert!(separation.invariant.kept_by(&idempotents));
+        assert_ne!(separation.invariant.value("ab"), separation.invariant.value("ba"));
+
+        assert!(separate(&commuting, &letters, "ab", "ba", &limits).is_none());
+    }
+
+    #[test]
+    fn rules_are_checked_one_by_one() {
+        let rules_addition = system(&["a", "b", "c"], "ab -> c\nc -> a\nba -> ab\n");
+        let srs = &rules_addition.srs;
+
+        let parikh = LinearWeight::parse("a=1 b=1 c=2", &srs.symbols).unwrap();
+        assert_eq!(parikh.to_string(), "#a + #b + 2*#c");
+        let report = srs.verify_invariant(&parikh);
+        assert!(report.breaking().is_empty());
+        assert!(!report.is_strict());
+        assert_eq!(
+            report.verdict(),
+            "W never increases, 2 of 3 rules keep it, so the decrease is only weak"
+        );
+
+        let report = srs.verify_invariant(&LinearWeight::parse("c=1", &srs.symbols).unwrap());
+        assert_eq!(report.breaking(), [0]);
+        assert_eq!(report.change(1), Change::Decreases);
+
+        assert!(LinearWeight::parse("a=1 a=2", &srs.symbols).is_err());
+        assert!(LinearWeight::parse("a", &srs.symbols).is_err());
+    }
+
+    #[test]
+    fn equations_are_checked_both_ways() {
+        let rules_addition = system(&["a", "b"], "ab = ba\naa = b\n");
+        let srs = &rules_addition.srs;
+        let report = srs.verify_invariant(&LinearWeight::parse("a=1 b=1", &srs.symbols).unwrap());
+        assert_eq!(report.rules.len(), 4);
+        assert_eq!(report.breaking().len(), 1);
+    }
+}
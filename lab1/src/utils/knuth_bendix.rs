@@ -0,0 +1,400 @@
+use crate::cli::{csv_writer, Format, Options};
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::srs::{occurrences, Srs};
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub(crate) struct CompletionLimits {
+    pub(crate) max_rules: usize,
+    pub(crate) max_rule_len: usize,
+    pub(crate) max_steps: usize,
+}
+
+impl Default for CompletionLimits {
+    fn default() -> Self {
+        Self {
+            max_rules: 100,
+            max_rule_len: 32,
+            max_steps: 1_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum StopReason {
+    TooManyRules(usize),
+    RuleTooLong(String, String),
+    StepBudgetExhausted(usize),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::TooManyRules(count) => write!(f, "rules limit exceeded ({count} rules)"),
+            StopReason::RuleTooLong(left, right) => write!(
+                f,
+                "rule length limit exceeded by {}",
+                RulesAddition::rule_line(left, right)
+            ),
+            StopReason::StepBudgetExhausted(steps) => {
+                write!(f, "step budget exhausted after {steps} rewrite steps")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CriticalPairKind {
+    Overlap,
+    Inclusion,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CriticalPair {
+    pub(crate) kind: CriticalPairKind,
+    pub(crate) first_rule: usize,
+    pub(crate) second_rule: usize,
+    pub(crate) position: usize,
+    pub(crate) word: String,
+    pub(crate) left: String,
+    pub(crate) right: String,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CompletionResult {
+    pub(crate) left_rules: Vec<String>,
+    pub(crate) right_rules: Vec<String>,
+    pub(crate) equations: Vec<(String, String)>,
+    pub(crate) rounds: usize,
+    pub(crate) steps: usize,
+    pub(crate) stopped: Option<StopReason>,
+}
+
+impl CompletionResult {
+    pub(crate) fn is_complete(&self) -> bool {
+        self.stopped.is_none()
+    }
+}
+
+impl RulesAddition {
+    pub(crate) fn critical_pairs(&self) -> Vec<CriticalPair> {
+        Self::critical_pairs_of(&self.rule_pairs())
+    }
+
+    // pairs between rules and both directions of every equation; an equation step only
+    // counts where it makes the word smaller, so the other pairs are never rewrites
+    pub(crate) fn ordered_critical_pairs(&self) -> Vec<CriticalPair> {
+        let mut steps = self.rule_pairs();
+        let rules = steps.len();
+        for (u, v) in &self.srs.equations {
+            steps.push((u.clone(), v.clone()));
+            steps.push((v.clone(), u.clone()));
+        }
+        Self::critical_pairs_of(&steps)
+            .into_iter()
+            .filter(|pair| {
+                (pair.first_rule < rules && pair.second_rule < rules)
+                    || (self.ordering.ground_greater(&pair.word, &pair.left)
+                        && self.ordering.ground_greater(&pair.word, &pair.right))
+            })
+            .collect()
+    }
+
+    fn rule_pairs(&self) -> Vec<(String, String)> {
+        self.srs.left_rules.iter().cloned().zip(self.srs.right_rules.iter().cloned()).collect()
+    }
+
+    fn critical_pairs_of(rules: &[(String, String)]) -> Vec<CriticalPair> {
+        let mut pairs = vec![];
+        for (i, (left_i, right_i)) in rules.iter().enumerate() {
+            if left_i.is_empty() {
+                continue;
+            }
+            for (j, (left_j, right_j)) in rules.iter().enumerate() {
+                if left_j.is_empty() {
+                    continue;
+                }
+
+                // suffix of left_i equals prefix of left_j
+                for k in 1..left_i.len().min(left_j.len()) {
+                    if !left_j.is_char_boundary(k) || !left_i.ends_with(&left_j[..k]) {
+                        continue;
+                    }
+                    let position = left_i.len() - k;
+                    let word = format!("{}{}", left_i, &left_j[k..]);
+                    pairs.push(CriticalPair {
+                        kind: CriticalPairKind::Overlap,
+                        first_rule: i,
+                        second_rule: j,
+                        position,
+                        left: Srs::rewrite(&word, 0, left_i, right_i),
+                        right: Srs::rewrite(&word, position, left_j, right_j),
+                        word,
+                    });
+                }
+
+                // left_j occurs inside left_i
+                if i == j || left_j.len() > left_i.len() || (left_j == left_i && j < i) {
+                    continue;
+                }
+                for position in occurrences(left_i, left_j) {
+                    let rewritten = Srs::rewrite(left_i, position, left_j, right_j);
+                    pairs.push(CriticalPair {
+                        kind: CriticalPairKind::Inclusion,
+                        first_rule: i,
+                        second_rule: j,
+                        position,
+                        word: left_i.clone(),
+                        left: right_i.clone(),
+                        right: rewritten,
+                    });
+                }
+            }
+        }
+        pairs
+    }
+
+    pub(crate) fn is_complete(&self) -> bool {
+        let terminating = self
+            .srs
+            .left_rules
+            .iter()
+            .zip(&self.srs.right_rules)
+            .all(|(left, right)| self.ordering.greater(left, right));
+        terminating
+            && self
+                .ordered_critical_pairs()
+                .iter()
+                .all(|pair| self.normalize(&pair.left) == self.normalize(&pair.right))
+    }
+
+    pub(crate) fn complete(&mut self, limits: &CompletionLimits) -> CompletionResult {
+        let mut rounds = 0;
+        let mut steps = 0;
+        let mut stopped = None;
+
+        let mut initial: VecDeque<(String, String)> = self
+            .srs
+            .left_rules
+            .drain(..)
+            .zip(self.srs.right_rules.drain(..))
+            .collect();
+        initial.make_contiguous().sort_by(|a, b| self.compare_shortlex(&a.0, &b.0));
+
+        if let Err(reason) = self.add_equations(initial, limits, &mut steps) {
+            stopped = Some(reason);
+        }
+
+        while stopped.is_none() {
+            rounds += 1;
+            let pairs = self.ordered_critical_pairs();
+            info!(
+                "Round {rounds}: {} rules, {} equations, {} critical pairs",
+                self.srs.left_rules.len(),
+                self.srs.equations.len(),
+                pairs.len()
+            );
+
+            let mut added = false;
+            for pair in pairs {
+                let origin = format!(
+                    "{:?} of rules {} and {} at {} in {}",
+                    pair.kind, pair.first_rule, pair.second_rule, pair.position, pair.word
+                );
+                let equation = VecDeque::from([(pair.left, pair.right)]);
+                match self.add_equations(equation, limits, &mut steps) {
+                    Ok(changed) => {
+                        if changed {
+                            info!("Resolved critical pair: {}", self.decode(&origin));
+                        }
+                        added |= changed;
+                    }
+                    Err(reason) => {
+                        stopped = Some(reason);
+                        break;
+                    }
+                }
+            }
+
+            if stopped.is_none() && !added {
+                info!("Completion finished after {rounds} rounds");
+                break;
+            }
+        }
+
+        if let Some(reason) = &stopped {
+            warn!("Completion stopped: {}", self.decode(&reason.to_string()));
+        }
+
+        CompletionResult {
+            left_rules: self.srs.left_rules.clone(),
+            right_rules: self.srs.right_rules.clone(),
+            equations: self.srs.equations.clone(),
+            rounds,
+            steps,
+            stopped,
+        }
+    }
+
+    fn add_equations(
+        &mut self,
+        mut equations: VecDeque<(String, String)>,
+        limits: &CompletionLimits,
+        steps: &mut usize,
+    ) -> Result<bool, StopReason> {
+        let mut added = false;
+        while let Some((u, v)) = equations.pop_front() {
+            let nu = self
+                .normalize_counted(&u, steps, limits.max_steps)
+                .ok_or(StopReason::StepBudgetExhausted(*steps))?;
+            let nv = self
+                .normalize_counted(&v, steps, limits.max_steps)
+                .ok_or(StopReason::StepBudgetExhausted(*steps))?;
+            if nu == nv {
+                continue;
+            }
+
+            let Some((left, right)) = self.orient(&nu, &nv) else {
+                if nu.len().max(nv.len()) > limits.max_rule_len {
+                    return Err(StopReason::RuleTooLong(nu, nv));
+                }
+                self.add_equation(&nu, &nv);
+                added = true;
+                if self.srs.left_rules.len() + self.srs.equations.len() > limits.max_rules {
+                    return Err(StopReason::TooManyRules(self.srs.left_rules.len() + self.srs.equations.len()));
+                }
+                continue;
+            };
+            if left.len() > limits.max_rule_len {
+                return Err(StopReason::RuleTooLong(left, right));
+            }
+            info!("Added rule {}", self.decode(&Self::rule_line(&left, &right)));
+            self.srs.left_rules.push(left.clone());
+            self.srs.right_rules.push(right);
+            added = true;
+
+            // interreduce the rest of the system with the new rule
+            let mut i = 0;
+            while i + 1 < self.srs.left_rules.len() {
+                if self.srs.left_rules[i].contains(left.as_str()) {
+                    let old_left = self.srs.left_rules.remove(i);
+                    let old_right = self.srs.right_rules.remove(i);
+                    info!("Removed rule {}", self.decode(&Self::rule_line(&old_left, &old_right)));
+                    equations.push_back((old_left, old_right));
+                    continue;
+                }
+                let right = self.srs.right_rules[i].clone();
+                self.srs.right_rules[i] = self
+                    .normalize_counted(&right, steps, limits.max_steps)
+                    .ok_or(StopReason::StepBudgetExhausted(*steps))?;
+                i += 1;
+            }
+            let mut i = 0;
+            while i < self.srs.equations.len() {
+                let (u, v) = &self.srs.equations[i];
+                if u.contains(left.as_str()) || v.contains(left.as_str()) {
+                    let (u, v) = self.srs.equations.remove(i);
+                    info!("Removed equation {}", self.decode(&Self::equation_line(&u, &v)));
+                    equations.push_back((u, v));
+                    continue;
+                }
+                i += 1;
+            }
+
+            if self.srs.left_rules.len() + self.srs.equations.len() > limits.max_rules {
+                return Err(StopReason::TooManyRules(self.srs.left_rules.len() + self.srs.equations.len()));
+            }
+        }
+        Ok(added)
+    }
+}
+
+pub fn start_knuth_bendix(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    info!("Letters read");
+
+    rules_addition.read_rules(&options.rules_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    }
+    info!("Rules read");
+
+    let result = rules_addition.complete(&options.completion);
+    let stopped = result.stopped.as_ref().map(|reason| rules_addition.decode(&reason.to_string()));
+    if options.format == Format::Csv {
+        if let Some(reason) = &stopped {
+            warn!("Partial system after {} rounds, stopped: {}", result.rounds, reason);
+        }
+        let mut writer = csv_writer();
+        let mut written = writer.write_record(["left", "right", "kind"]);
+        for (left, right) in result.left_rules.iter().zip(result.right_rules.iter()) {
+            written = written.and_then(|_| {
+                writer.write_record([rules_addition.decode(left), rules_addition.decode(right), "rule".into()])
+            });
+        }
+        for (u, v) in &result.equations {
+            written = written.and_then(|_| {
+                writer.write_record([rules_addition.decode(u), rules_addition.decode(v), "equation".into()])
+            });
+        }
+        if let Err(e) = written.and_then(|_| writer.flush().map_err(csv::Error::from)) {
+            return Err(format!("Error while writing csv {e}"));
+        }
+        return Ok(());
+    }
+    if result.is_complete() {
+        println!(
+            "Completed system ({} rounds, {} rewrite steps):",
+            result.rounds, result.steps
+        );
+    } else if let Some(reason) = &stopped {
+        println!(
+            "Partial system after {} rounds, stopped: {}",
+            result.rounds, reason
+        );
+    }
+    for (left, right) in result.left_rules.iter().zip(result.right_rules.iter()) {
+        println!("{}", rules_addition.decode(&RulesAddition::rule_line(left, right)));
+    }
+    for (u, v) in &result.equations {
+        println!("{}", rules_addition.decode(&RulesAddition::equation_line(u, v)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn completion_adds_the_missing_rules() {
+        let mut rules_addition = system(&["a", "b"], "aaa -> .\nbab -> a\n");
+        let result = rules_addition.complete(&CompletionLimits::default());
+        assert!(result.is_complete());
+        let rules: Vec<String> = result
+            .left_rules
+            .iter()
+            .zip(&result.right_rules)
+            .map(|(left, right)| RulesAddition::rule_line(left, right))
+            .collect();
+        assert_eq!(rules, ["aaa -> .", "ba -> ab", "bb -> ."]);
+    }
+
+    #[test]
+    fn completion_stops_at_the_rules_limit() {
+        let mut rules_addition = system(&["a", "b"], "aaa -> .\nbab -> a\n");
+        let limits = CompletionLimits {
+            max_rules: 2,
+            ..CompletionLimits::default()
+        };
+        let result = rules_addition.complete(&limits);
+        assert!(matches!(result.stopped, Some(StopReason::TooManyRules(_))));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::cli::Options;
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::srs::{occurrences, Srs};
+use log::info;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RewriteStep {
+    pub(crate) left: String,
+    pub(crate) right: String,
+    pub(crate) position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoopWitness {
+    pub(crate) words: Vec<String>,
+    pub(crate) steps: Vec<RewriteStep>,
+}
+
+impl LoopWitness {
+    pub(crate) fn start(&self) -> &str {
+        &self.words[0]
+    }
+
+    pub(crate) fn end(&self) -> &str {
+        self.words.last().unwrap()
+    }
+
+    pub(crate) fn factor_position(&self) -> usize {
+        self.end().find(self.start()).unwrap()
+    }
+}
+
+impl fmt::Display for LoopWitness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.words[0])?;
+        for (step, word) in self.steps.iter().zip(&self.words[1..]) {
+            write!(
+                f,
+                " -> ({}) -> {}",
+                RulesAddition::rule_line(&step.left, &step.right),
+                word
+            )?;
+        }
+        if self.start() != self.end() {
+            write!(
+                f,
+                ", {} occurs in {} at position {}",
+                self.start(),
+                self.end(),
+                self.factor_position()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LoopSearchLimits {
+    pub(crate) max_closures: usize,
+    pub(crate) max_word_len: usize,
+}
+
+impl Default for LoopSearchLimits {
+    fn default() -> Self {
+        Self {
+            max_closures: 100_000,
+            max_word_len: 32,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum LoopSearch {
+    Loop(LoopWitness),
+    NoLoop { closures: usize },
+    Inconclusive { closures: usize },
+}
+
+#[derive(Clone)]
+struct Closure {
+    words: Vec<String>,
+    steps: Vec<RewriteStep>,
+}
+
+impl Closure {
+    fn extend(&self, suffix: &str, step: RewriteStep, word: String) -> Self {
+        let mut words: Vec<String> = self.words.iter().map(|w| format!("{w}{suffix}")).collect();
+        words.push(word);
+        let mut steps = self.steps.clone();
+        steps.push(step);
+        Self { words, steps }
+    }
+
+    fn recurrence(&self) -> Option<LoopWitness> {
+        let (last, earlier) = self.words.split_last()?;
+        let i = earlier
+            .iter()
+            .position(|word| !word.is_empty() && last.contains(word.as_str()))?;
+        Some(LoopWitness {
+            words: self.words[i..].to_vec(),
+            steps: self.steps[i..].to_vec(),
+        })
+    }
+}
+
+impl RulesAddition {
+    pub(crate) fn rewrite_step(&self, from: &str, to: &str) -> Option<RewriteStep> {
+        let equation_step = self
+            .equation_steps(from)
+            .into_iter()
+            .find(|(_, next)| next == to)
+            .map(|(step, _)| step);
+        for (left, right) in self.srs.left_rules.iter().zip(&self.srs.right_rules) {
+            for position in occurrences(from, left) {
+                if Srs::rewrite(from, position, left, right) == to {
+                    return Some(RewriteStep {
+                        left: left.clone(),
+                        right: right.clone(),
+                        position,
+                    });
+                }
+            }
+        }
+        equation_step
+    }
+
+    pub(crate) fn loop_in_history(&self, history: &[String], word: &str) -> Option<LoopWitness> {
+        let i = history
+            .iter()
+            .position(|earlier| !earlier.is_empty() && word.contains(earlier.as_str()))?;
+        let mut words = history[i..].to_vec();
+        words.push(word.to_string());
+        let steps = words
+            .windows(2)
+            .map(|pair| self.rewrite_step(&pair[0], &pair[1]).unwrap())
+            .collect();
+        Some(LoopWitness { words, steps })
+    }
+
+    pub(crate) fn find_loop(&self, limits: &LoopSearchLimits) -> LoopSearch {
+        let rules: Vec<(&String, &String)> = self
+            .srs
+            .left_rules
+            .iter()
+            .zip(&self.srs.right_rules)
+            .filter(|(left, _)| !left.is_empty())
+            .collect();
+
+        let mut queue: VecDeque<Closure> = rules
+            .iter()
+            .map(|(left, right)| Closure {
+                words: vec![left.to_string(), right.to_string()],
+                steps: vec![RewriteStep {
+                    left: left.to_string(),
+                    right: right.to_string(),
+                    position: 0,
+                }],
+            })
+            .collect();
+        let mut visited: HashSet<(String, String)> = HashSet::new();
+        let mut closures = 0;
+        let mut truncated = false;
+
+        while let Some(closure) = queue.pop_front() {
+            if let Some(witness) = closure.recurrence() {
+                return LoopSearch::Loop(witness);
+            }
+            let key = (closure.words[0].clone(), closure.words.last().unwrap().clone());
+            if !visited.insert(key) {
+                continue;
+            }
+            closures += 1;
+            if closures > limits.max_closures {
+                return LoopSearch::Inconclusive { closures };
+            }
+
+            let current = closure.words.last().unwrap();
+            for (left, right) in &rules {
+                for position in occurrences(current, left) {
+                    let word = Srs::rewrite(current, position, left, right);
+                    if word.len() > limits.max_word_len {
+                        truncated = true;
+                        continue;
+                    }
+                    let step = RewriteStep {
+                        left: left.to_string(),
+                        right: right.to_string(),
+                        position,
+                    };
+                    queue.push_back(closure.extend("", step, word));
+                }
+
+                // a suffix of the current word is a proper prefix of the left side
+                for k in 1..left.len() {
+                    if k > current.len() || !left.is_char_boundary(k) || !current.ends_with(&left[..k]) {
+                        continue;
+                    }
+                    let suffix = &left[k..];
+                    let position = current.len() - k;
+                    let word = Srs::rewrite(&format!("{current}{suffix}"), position, left, right);
+                    if word.len() > limits.max_word_len
+                        || closure.words[0].len() + suffix.len() > limits.max_word_len
+                    {
+                        truncated = true;
+                        continue;
+                    }
+                    let step = RewriteStep {
+                        left: left.to_string(),
+                        right: right.to_string(),
+                        position,
+                    };
+                    queue.push_back(closure.extend(suffix, step, word));
+                }
+            }
+        }
+
+        if truncated {
+            LoopSearch::Inconclusive { closures }
+        } else {
+            LoopSearch::NoLoop { closures }
+        }
+    }
+}
+
+pub fn start_loop_finder(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    let Some(rules) = rules_addition.read_raw_rules(&options.rules_path) else {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    };
+    rules_addition.set_rules(rules);
+    info!("Rules read");
+
+    if let Some(equations) = rules_addition.equation_lines() {
+        println!("Equations ignored, the search covers the rules only: {}", rules_addition.decode(&equations));
+    }
+    match rules_addition.find_loop(&LoopSearchLimits::default()) {
+        LoopSearch::Loop(witness) => {
+            println!("Loop found: {}", rules_addition.decode(&witness.to_string()))
+        }
+        LoopSearch::NoLoop { closures } => {
+            println!("No loop: all {closures} forward closures explored")
+        }
+        LoopSearch::Inconclusive { closures } => {
+            println!("No loop found within {closures} forward closures")
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    fn search(source: &str) -> LoopSearch {
+        system(&["a", "b"], source).find_loop(&LoopSearchLimits::default())
+    }
+
+    #[test]
+    fn loops_are_found_with_a_witness() {
+        let LoopSearch::Loop(witness) = search("ab -> bab\n") else {
+            panic!("ab -> bab loops");
+        };
+        assert_eq!(witness.to_string(), "ab -> (ab -> bab) -> bab, ab occurs in bab at position 1");
+
+        let LoopSearch::Loop(witness) = search("ab -> ba\nba -> ab\n") else {
+            panic!("ab -> ba and ba -> ab loop");
+        };
+        assert_eq!(witness.start(), witness.end());
+        assert_eq!(witness.steps.len(), 2);
+
+        assert!(!matches!(search("ba -> ab\n"), LoopSearch::Loop(_)));
+    }
+}
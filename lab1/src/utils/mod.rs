@@ -1 +1,17 @@
-pub mod rules_additioner;
\ No newline at end of file
+pub mod rules_additioner;
+pub mod knuth_bendix;
+pub mod confluence;
+pub mod orderings;
+pub mod termination;
+pub mod loops;
+pub mod normalizer;
+pub mod normal_forms;
+pub mod word_problem;
+pub mod srs_file;
+pub mod interreduction;
+pub mod provenance;
+pub mod symbols;
+pub mod shortlex;
+pub mod srs;
+pub mod redundancy;
+pub mod invariants;
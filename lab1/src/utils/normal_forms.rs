@@ -0,0 +1,315 @@
+use crate::cli::{csv_writer, Format, Options};
+use crate::utils::normalizer::{Normalizer, ROOT};
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::symbols::Symbols;
+use crate::utils::shortlex::ShortlexWords;
+use crate::utils::word_problem::Direction;
+use log::{info, warn};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Debug, Clone)]
+pub(crate) struct NormalFormAutomaton {
+    pub(crate) letters: Vec<String>,
+    pub(crate) transitions: Vec<Vec<Option<usize>>>,
+}
+
+impl NormalFormAutomaton {
+    // states are the irreducible states of the left-side automaton, so every state accepts
+    pub(crate) fn new(normalizer: &Normalizer, letters: Vec<String>) -> Self {
+        let mut ids: HashMap<usize, usize> = HashMap::from([(ROOT, 0)]);
+        let mut order = vec![ROOT];
+        let mut transitions = vec![];
+        let mut i = 0;
+        while i < order.len() {
+            let source = order[i];
+            let mut row = vec![];
+            for letter in &letters {
+                let mut state = Some(source);
+                for byte in letter.bytes() {
+                    state = state
+                        .map(|state| normalizer.step(state, byte))
+                        .filter(|&state| !normalizer.is_reducible(state));
+                }
+                row.push(state.map(|state| {
+                    *ids.entry(state).or_insert_with(|| {
+                        order.push(state);
+                        order.len() - 1
+                    })
+                }));
+            }
+            transitions.push(row);
+            i += 1;
+        }
+        Self {
+            letters,
+            transitions,
+        }
+    }
+
+    pub(crate) fn state_count(&self) -> usize {
+        self.transitions.len()
+    }
+
+    // the longest normal form, or None when there is a cycle and the language is infinite
+    pub(crate) fn longest(&self) -> Option<usize> {
+        // 0 = unvisited, 1 = on the stack, 2 = done
+        let mut marks = vec![0u8; self.state_count()];
+        let mut depth = vec![0usize; self.state_count()];
+        let mut stack = vec![(0usize, 0usize)];
+        marks[0] = 1;
+        while let Some((state, next_letter)) = stack.pop() {
+            if next_letter == self.letters.len() {
+                marks[state] = 2;
+                depth[state] = self.transitions[state]
+                    .iter()
+                    .flatten()
+                    .map(|&target| depth[target] + 1)
+                    .max()
+                    .unwrap_or(0);
+                continue;
+            }
+            stack.push((state, next_letter + 1));
+            if let Some(target) = self.transitions[state][next_letter] {
+                match marks[target] {
+                    0 => {
+                        marks[target] = 1;
+                        stack.push((target, 0));
+                    }
+                    1 => return None,
+                    _ => {}
+                }
+            }
+        }
+        Some(depth[0])
+    }
+
+    pub(crate) fn counts_by_length(&self, max_len: usize) -> Vec<u128> {
+        let mut current = vec![0u128; self.state_count()];
+        current[0] = 1;
+        let mut counts = vec![1];
+        for _ in 0..max_len {
+            let mut next = vec![0u128; self.state_count()];
+            for (state, &count) in current.iter().enumerate() {
+                for &target in self.transitions[state].iter().flatten() {
+                    next[target] = next[target].saturating_add(count);
+                }
+            }
+            counts.push(next.iter().fold(0u128, |sum, &count| sum.saturating_add(count)));
+            current = next;
+        }
+        counts
+    }
+
+    pub(crate) fn count(&self) -> Option<u128> {
+        let longest = self.longest()?;
+        Some(self.counts_by_length(longest).into_iter().sum())
+    }
+
+    // normal forms up to max_len in shortlex order of the alphabet
+    pub(crate) fn enumerate(&self, max_len: usize) -> Vec<String> {
+        ShortlexWords::new(self, max_len, 0, 0).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct EquivalenceClass {
+    pub(crate) normal_form: String,
+    pub(crate) words: Vec<String>,
+    pub(crate) counts: Vec<usize>,
+}
+
+impl RulesAddition {
+    pub(crate) fn normal_form_automaton(&self) -> NormalFormAutomaton {
+        NormalFormAutomaton::new(&self.normalizer(), self.alphabet())
+    }
+
+    // words up to max_len that rewrite to normal_form, found by applying rules right to left
+    pub(crate) fn equivalence_class(&self, normal_form: &str, max_len: usize) -> EquivalenceClass {
+        let mut seen = HashSet::from([normal_form.to_string()]);
+        let mut queue = VecDeque::from([normal_form.to_string()]);
+        while let Some(word) = queue.pop_front() {
+            for step in self.thue_neighbours(&word) {
+                // rules are walked backwards from the normal form, equations either way
+                let equation = self
+                    .srs
+                    .equations
+                    .iter()
+                    .any(|(left, right)| *left == step.left && *right == step.right);
+                if (equation || step.direction == Direction::Backward)
+                    && step.word.len() <= max_len
+                    && seen.insert(step.word.clone())
+                {
+                    queue.push_back(step.word);
+                }
+            }
+        }
+
+        let mut words: Vec<String> = seen.into_iter().filter(|word| word.len() <= max_len).collect();
+        words.sort_by(|a, b| self.compare_shortlex(a, b));
+        let mut counts = vec![0; max_len + 1];
+        for word in &words {
+            counts[word.len()] += 1;
+        }
+        EquivalenceClass {
+            normal_form: normal_form.to_string(),
+            words,
+            counts,
+        }
+    }
+}
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+fn write_words(words: &[String], symbols: &Symbols) -> Result<(), String> {
+    let mut writer = csv_writer();
+    let mut written = writer.write_record(["word", "length"]);
+    for word in words {
+        written = written.and_then(|_| writer.write_record([symbols.decode(word), word.len().to_string()]));
+    }
+    written
+        .and_then(|_| writer.flush().map_err(csv::Error::from))
+        .map_err(|e| format!("Error while writing csv {e}"))
+}
+
+pub fn start_equivalence_class(word: &str, options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    rules_addition.read_rules(&options.rules_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    }
+    let word = rules_addition.encode(word)?;
+
+    if !rules_addition.is_complete() {
+        warn!("System is not complete, the class may be missing words");
+    }
+    if rules_addition
+        .srs
+        .left_rules
+        .iter()
+        .zip(&rules_addition.srs.right_rules)
+        .any(|(left, right)| left.len() < right.len())
+    {
+        warn!("Some rules increase length, words reachable only through longer words are missing");
+    }
+    let normal_form = rules_addition.normalize(&word);
+    if normal_form != word {
+        info!(
+            "{} is reducible, using its normal form {}",
+            rules_addition.decode(show(&word)),
+            rules_addition.decode(show(&normal_form))
+        );
+    }
+
+    let max_len = options.max_len.unwrap_or(rules_addition.srs.max_len);
+    let class = rules_addition.equivalence_class(&normal_form, max_len);
+    if options.format == Format::Csv {
+        return write_words(&class.words, &rules_addition.srs.symbols);
+    }
+    println!(
+        "Class of {} up to length {max_len}: {} words",
+        rules_addition.decode(show(&class.normal_form)),
+        class.words.len()
+    );
+    for (length, count) in class.counts.iter().enumerate() {
+        println!("length {length}: {count}");
+    }
+    let words: Vec<&str> = class.words.iter().map(|word| show(word)).collect();
+    println!("{}", rules_addition.decode(&words.join(" ")));
+    Ok(())
+}
+
+pub fn start_normal_forms(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    let Some(rules) = rules_addition.read_raw_rules(&options.rules_path) else {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    };
+    rules_addition.set_rules(rules);
+    info!("Rules read");
+
+    let automaton = rules_addition.normal_form_automaton();
+    info!("Automaton of irreducible words has {} states", automaton.state_count());
+
+    let longest = automaton.longest();
+    if options.format == Format::Csv {
+        let max_len = longest.unwrap_or(options.max_len.unwrap_or(rules_addition.srs.max_len));
+        return write_words(&automaton.enumerate(max_len), &rules_addition.srs.symbols);
+    }
+    match longest {
+        Some(longest) => {
+            let normal_forms = automaton.enumerate(longest);
+            println!(
+                "Finite: {} normal forms, the longest has length {longest}",
+                automaton.count().unwrap_or_default()
+            );
+            for (length, count) in automaton.counts_by_length(longest).iter().enumerate() {
+                println!("length {length}: {count}");
+            }
+            let normal_forms: Vec<&str> = normal_forms.iter().map(|word| show(word)).collect();
+            println!("{}", rules_addition.decode(&normal_forms.join(" ")));
+        }
+        None => {
+            println!("Infinite: counts of normal forms by length");
+            for (length, count) in automaton
+                .counts_by_length(options.max_len.unwrap_or(rules_addition.srs.max_len))
+                .iter()
+                .enumerate()
+            {
+                println!("length {length}: {count}");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn automaton_counts_normal_forms() {
+        let finite = system(&["a", "b"], "aa -> a\nbb -> b\nba -> ab\n").normal_form_automaton();
+        assert_eq!(finite.longest(), Some(2));
+        assert_eq!(finite.count(), Some(4));
+        assert_eq!(finite.enumerate(2), ["", "a", "b", "ab"]);
+
+        let infinite = system(&["a", "b"], "ba -> ab\n").normal_form_automaton();
+        assert_eq!(infinite.longest(), None);
+        assert_eq!(infinite.count(), None);
+        assert_eq!(infinite.counts_by_length(3), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn class_is_listed_in_shortlex_order() {
+        let rules_addition = system(&["a", "b"], "ba -> ab\n");
+        let class = rules_addition.equivalence_class("aab", 3);
+        assert_eq!(class.words, ["aab", "aba", "baa"]);
+        assert_eq!(class.counts, [0, 0, 0, 3]);
+
+        let rules_addition = system(&["a", "x1"], "x1 -> a\n");
+        let class = rules_addition.equivalence_class("aa", 2);
+        let words: Vec<String> = class.words.iter().map(|word| rules_addition.decode(word)).collect();
+        assert_eq!(words, ["aa", "ax1", "x1a", "x1x1"]);
+        assert_eq!(class.counts, [0, 0, 4]);
+    }
+
+    #[test]
+    fn class_follows_equations_both_ways() {
+        let rules_addition = system(&["a", "b"], "ordering: weight weights a=1 b=1\nab = ba\n");
+        assert_eq!(rules_addition.equivalence_class("ab", 2).words, ["ab", "ba"]);
+        assert_eq!(rules_addition.equivalence_class("ab", 3).words, ["ab", "ba"]);
+
+        let rules_addition = system(&["a", "b"], "ordering: weight weights a=1 b=1\nab = ba\nbb -> a\n");
+        let class = rules_addition.equivalence_class("a", 3);
+        assert_eq!(class.words, ["a", "bb"]);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::cli::{csv_writer, seeded_rng, Format, Options};
+use crate::utils::rules_additioner::RulesAddition;
+use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+pub(crate) const ROOT: usize = 0;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Normalizer {
+    classes: [usize; 256],
+    class_count: usize,
+    delta: Vec<usize>,
+    matched: Vec<Option<usize>>,
+    left_lens: Vec<usize>,
+    right_rules: Vec<Vec<u8>>,
+}
+
+impl Normalizer {
+    pub(crate) fn new(left_rules: &[String], right_rules: &[String]) -> Self {
+        // bytes that never occur in a left side share class 0 and always lead back to the root
+        let mut classes = [0usize; 256];
+        let mut class_count = 1;
+        for left in left_rules {
+            for &byte in left.as_bytes() {
+                if classes[byte as usize] == 0 {
+                    classes[byte as usize] = class_count;
+                    class_count += 1;
+                }
+            }
+        }
+
+        let mut trie: Vec<HashMap<usize, usize>> = vec![HashMap::new()];
+        let mut matched: Vec<Option<usize>> = vec![None];
+        for (rule_id, left) in left_rules.iter().enumerate() {
+            if left.is_empty() {
+                continue;
+            }
+            let mut state = ROOT;
+            for &byte in left.as_bytes() {
+                let class = classes[byte as usize];
+                state = match trie[state].get(&class) {
+                    Some(&next) => next,
+                    None => {
+                        trie.push(HashMap::new());
+                        matched.push(None);
+                        let next = trie.len() - 1;
+                        trie[state].insert(class, next);
+                        next
+                    }
+                };
+            }
+            if matched[state].is_none() {
+                matched[state] = Some(rule_id);
+            }
+        }
+
+        let mut delta = vec![ROOT; trie.len() * class_count];
+        let mut fail = vec![ROOT; trie.len()];
+        let mut queue = VecDeque::new();
+        for class in 0..class_count {
+            if let Some(&next) = trie[ROOT].get(&class) {
+                delta[ROOT * class_count + class] = next;
+                queue.push_back(next);
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            let fallback = fail[state];
+            if matched[state].is_none() {
+                matched[state] = matched[fallback];
+            } else if let Some(rule_id) = matched[fallback] {
+                matched[state] = matched[state].map(|own| own.min(rule_id));
+            }
+            for class in 0..class_count {
+                match trie[state].get(&class) {
+                    Some(&next) => {
+                        fail[next] = delta[fallback * class_count + class];
+                        delta[state * class_count + class] = next;
+                        queue.push_back(next);
+                    }
+                    None => {
+                        delta[state * class_count + class] = delta[fallback * class_count + class];
+                    }
+                }
+            }
+        }
+
+        Self {
+            classes,
+            class_count,
+            delta,
+            matched,
+            left_lens: left_rules.iter().map(String::len).collect(),
+            right_rules: right_rules.iter().map(|right| right.as_bytes().to_vec()).collect(),
+        }
+    }
+
+    pub(crate) fn step(&self, state: usize, byte: u8) -> usize {
+        self.delta[state * self.class_count + self.classes[byte as usize]]
+    }
+
+    pub(crate) fn is_reducible(&self, state: usize) -> bool {
+        self.matched[state].is_some()
+    }
+
+    pub(crate) fn state_count(&self) -> usize {
+        self.matched.len()
+    }
+
+    pub(crate) fn normalize(&self, word: &str) -> String {
+        self.normalize_counted(word, usize::MAX)
+            .map(|(normal_form, _)| normal_form)
+            .unwrap_or_else(|| word.to_string())
+    }
+
+    // Book's algorithm: the stack holds an irreducible prefix, so after a rewrite
+    // only the right side has to be fed through the automaton again
+    pub(crate) fn normalize_counted(&self, word: &str, max_steps: usize) -> Option<(String, usize)> {
+        let mut input: Vec<u8> = word.bytes().rev().collect();
+        let mut output: Vec<u8> = Vec::with_capacity(word.len());
+        let mut states: Vec<usize> = Vec::with_capacity(word.len() + 1);
+        states.push(ROOT);
+        let mut steps = 0;
+
+        while let Some(byte) = input.pop() {
+            let state = self.step(states[states.len() - 1], byte);
+            output.push(byte);
+            states.push(state);
+
+            if let Some(rule_id) = self.matched[state] {
+                if steps >= max_steps {
+                    return None;
+                }
+                steps += 1;
+                let len = self.left_lens[rule_id];
+                output.truncate(output.len() - len);
+                states.truncate(states.len() - len);
+                input.extend(self.right_rules[rule_id].iter().rev());
+            }
+        }
+
+        Some((String::from_utf8(output).expect("rules keep words valid UTF-8"), steps))
+    }
+}
+
+impl RulesAddition {
+    pub(crate) fn normalizer(&self) -> Normalizer {
+        let (mut left_rules, mut right_rules) = (self.srs.left_rules.clone(), self.srs.right_rules.clone());
+        for (left, right) in self.oriented_equations() {
+            left_rules.push(left);
+            right_rules.push(right);
+        }
+        Normalizer::new(&left_rules, &right_rules)
+    }
+
+    fn random_word(&self, length: usize, rnd: &mut StdRng) -> String {
+        (0..length)
+            .map(|_| self.letters[&rnd.gen_range(0..self.alphabet_len)].as_str())
+            .collect()
+    }
+}
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+pub fn start_normalize(word: &str, options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    rules_addition.read_rules(&options.rules_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    }
+    let word = rules_addition.encode(word)?;
+
+    if !rules_addition.is_complete() {
+        warn!("System is not complete, the normal form depends on the rewriting strategy");
+    }
+    let Some((normal_form, steps)) =
+        rules_addition.normalizer().normalize_counted(&word, options.completion.max_steps)
+    else {
+        return Err(format!("No normal form within {} rewrite steps", options.completion.max_steps));
+    };
+
+    match options.format {
+        Format::Text => println!(
+            "{} -> {} ({steps} steps)",
+            rules_addition.decode(show(&word)),
+            rules_addition.decode(show(&normal_form))
+        ),
+        Format::Csv => {
+            let mut writer = csv_writer();
+            let written = writer
+                .write_record(["word", "normal_form", "steps"])
+                .and_then(|_| {
+                    writer.write_record([
+                        rules_addition.decode(&word),
+                        rules_addition.decode(&normal_form),
+                        steps.to_string(),
+                    ])
+                })
+                .and_then(|_| writer.flush().map_err(csv::Error::from));
+            if let Err(e) = written {
+                return Err(format!("Error while writing csv {e}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+pub fn start_normalizer_benchmark(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    rules_addition.read_rules(&options.rules_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    }
+
+    let completion = rules_addition.complete(&options.completion);
+    if let Some(reason) = completion.stopped {
+        let reason = rules_addition.decode(&reason.to_string());
+        warn!("System is not complete ({reason}), normal forms may differ");
+    }
+    info!("Benchmarking {} rules", rules_addition.srs.left_rules.len());
+
+    let mut rnd = seeded_rng(options);
+    let (normalizer, build_time) = time(|| rules_addition.normalizer());
+    println!("Automaton with {} states built in {build_time:?}", normalizer.state_count());
+    println!("length | get_normal_forms | leftmost rewriting | automaton | steps");
+
+    for length in [4, 6, 8, 100, 1_000, 5_000, 10_000] {
+        let word = rules_addition.random_word(length, &mut rnd);
+
+        let old = if length <= 8 {
+            let (forms, elapsed) =
+                time(|| rules_addition.get_normal_forms(&word, vec![], &mut HashMap::new()));
+            match forms {
+                Ok(forms) if forms.len() != 1 => {
+                    warn!("{} has {} normal forms", rules_addition.decode(&word), forms.len())
+                }
+                Err(witness) => {
+                    warn!("Rewriting does not terminate: {}", rules_addition.decode(&witness.to_string()))
+                }
+                _ => {}
+            }
+            format!("{elapsed:?}")
+        } else {
+            "skipped".to_string()
+        };
+
+        let leftmost = if length <= 1_000 {
+            let (form, elapsed) = time(|| rules_addition.normalize(&word));
+            if form != normalizer.normalize(&word) {
+                warn!("Normalizers disagree on {}", rules_addition.decode(&word));
+            }
+            format!("{elapsed:?}")
+        } else {
+            "skipped".to_string()
+        };
+
+        let ((_, steps), elapsed) = time(|| normalizer.normalize_counted(&word, usize::MAX).unwrap());
+        println!("{length} | {old} | {leftmost} | {elapsed:?} | {steps}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn automaton_agrees_with_leftmost_rewriting() {
+        let rules_addition = system(&["a", "b"], "aaa -> .\nba -> ab\nbb -> .\n");
+        let normalizer = rules_addition.normalizer();
+        let mut words = vec![String::new()];
+        for _ in 0..6 {
+            words = words.iter().flat_map(|word| [format!("{word}a"), format!("{word}b")]).collect();
+            for word in &words {
+                assert_eq!(normalizer.normalize(word), rules_addition.normalize(word), "{word}");
+            }
+        }
+        assert_eq!(normalizer.normalize_counted("bbaba", 10), Some(("aab".to_string(), 2)));
+    }
+
+    #[test]
+    fn rewriting_stops_at_the_step_budget() {
+        let rules_addition = system(&["a", "b"], "a -> b\nb -> a\n");
+        assert_eq!(rules_addition.normalizer().normalize_counted("ab", 10), None);
+    }
+}
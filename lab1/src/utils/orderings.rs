@@ -0,0 +1,431 @@
+use crate::utils::symbols::Symbols;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+pub(crate) trait ReductionOrder: fmt::Debug + Send + Sync {
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering>;
+
+    fn declaration(&self) -> String;
+
+    fn greater(&self, a: &str, b: &str) -> bool {
+        self.compare(a, b) == Some(Ordering::Greater)
+    }
+
+    // the total extension equations are applied along: words the ordering cannot
+    // separate compare by shortlex
+    fn ground_greater(&self, a: &str, b: &str) -> bool {
+        match self.compare(a, b) {
+            Some(Ordering::Greater) => true,
+            Some(Ordering::Less) => false,
+            _ => a.len().cmp(&b.len()).then_with(|| a.cmp(b)) == Ordering::Greater,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Precedence {
+    ranks: HashMap<char, usize>,
+    highest_first: Vec<char>,
+    alphabet: Vec<char>,
+}
+
+impl Precedence {
+    pub(crate) fn new(highest_first: Vec<char>) -> Self {
+        let ranks = highest_first
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(rank, &letter)| (letter, rank))
+            .collect();
+        Self {
+            ranks,
+            highest_first,
+            alphabet: vec![],
+        }
+    }
+
+    // letters in alphabet order, for the ones the precedence leaves out
+    pub(crate) fn with_alphabet(mut self, alphabet: &[char]) -> Self {
+        self.alphabet = alphabet.to_vec();
+        self
+    }
+
+    // undeclared letters sit below declared ones in alphabet order,
+    // letters outside the alphabet come last by code point
+    fn key(&self, letter: char) -> (bool, usize) {
+        match self.ranks.get(&letter) {
+            Some(&rank) => (true, rank),
+            None => match self.alphabet.iter().position(|&known| known == letter) {
+                Some(position) => (false, position),
+                None => (false, self.alphabet.len() + letter as usize),
+            },
+        }
+    }
+
+    pub(crate) fn compare_letters(&self, a: char, b: char) -> Ordering {
+        self.key(a).cmp(&self.key(b))
+    }
+
+    // one number per letter that orders letters the way `compare_letters` does
+    fn rank(&self, letter: char) -> usize {
+        match self.key(letter) {
+            (true, rank) => self.alphabet.len() + char::MAX as usize + 1 + rank,
+            (false, code) => code,
+        }
+    }
+
+    fn compare_lex(&self, a: &[char], b: &[char]) -> Ordering {
+        for (&x, &y) in a.iter().zip(b) {
+            let ordering = self.compare_letters(x, y);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        a.len().cmp(&b.len())
+    }
+
+    fn compare_shortlex(&self, a: &[char], b: &[char]) -> Ordering {
+        a.len().cmp(&b.len()).then_with(|| self.compare_lex(a, b))
+    }
+
+    fn declaration(&self) -> String {
+        self.highest_first
+            .iter()
+            .map(|letter| letter.to_string())
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+fn with_precedence(kind: &str, precedence: &Precedence) -> String {
+    if precedence.highest_first.is_empty() {
+        kind.to_string()
+    } else {
+        format!("{kind} {}", precedence.declaration())
+    }
+}
+
+fn letter_values(name: &str, values: &HashMap<char, usize>) -> String {
+    let mut values: Vec<_> = values.iter().collect();
+    values.sort();
+    let values: Vec<String> = values
+        .into_iter()
+        .map(|(letter, value)| format!("{letter}={value}"))
+        .collect();
+    format!("{name} {}", values.join(" "))
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Shortlex {
+    pub(crate) precedence: Precedence,
+}
+
+impl ReductionOrder for Shortlex {
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering> {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        Some(self.precedence.compare_shortlex(&a, &b))
+    }
+
+    fn declaration(&self) -> String {
+        with_precedence("shortlex", &self.precedence)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WeightedLex {
+    pub(crate) precedence: Precedence,
+    pub(crate) weights: HashMap<char, usize>,
+}
+
+fn weight(weights: &HashMap<char, usize>, word: &str) -> usize {
+    word.chars()
+        .map(|letter| weights.get(&letter).copied().unwrap_or(1))
+        .sum()
+}
+
+impl WeightedLex {
+    pub(crate) fn weight(&self, word: &str) -> usize {
+        weight(&self.weights, word)
+    }
+}
+
+impl ReductionOrder for WeightedLex {
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering> {
+        let weights = self.weight(a).cmp(&self.weight(b));
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        Some(weights.then_with(|| self.precedence.compare_shortlex(&a, &b)))
+    }
+
+    fn declaration(&self) -> String {
+        format!(
+            "{} {}",
+            with_precedence("wtlex", &self.precedence),
+            letter_values("weights", &self.weights)
+        )
+    }
+}
+
+// words of equal weight are incomparable, so equations like `ab = ba` stay unoriented
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Weight {
+    pub(crate) weights: HashMap<char, usize>,
+}
+
+impl ReductionOrder for Weight {
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering> {
+        match weight(&self.weights, a).cmp(&weight(&self.weights, b)) {
+            Ordering::Equal if a != b => None,
+            ordering => Some(ordering),
+        }
+    }
+
+    fn declaration(&self) -> String {
+        if self.weights.is_empty() {
+            "weight".to_string()
+        } else {
+            format!("weight {}", letter_values("weights", &self.weights))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RecursivePath {
+    pub(crate) precedence: Precedence,
+}
+
+impl RecursivePath {
+    fn compare_chars(&self, u: &[char], v: &[char]) -> Ordering {
+        match (u.split_last(), v.split_last()) {
+            (None, None) => Ordering::Equal,
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (Some((&a, u_rest)), Some((&b, v_rest))) => match self.precedence.compare_letters(a, b) {
+                Ordering::Equal => self.compare_chars(u_rest, v_rest),
+                Ordering::Greater => match self.compare_chars(u, v_rest) {
+                    Ordering::Greater => Ordering::Greater,
+                    _ => Ordering::Less,
+                },
+                Ordering::Less => match self.compare_chars(u_rest, v) {
+                    Ordering::Less => Ordering::Less,
+                    _ => Ordering::Greater,
+                },
+            },
+        }
+    }
+}
+
+impl ReductionOrder for RecursivePath {
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering> {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        Some(self.compare_chars(&a, &b))
+    }
+
+    fn declaration(&self) -> String {
+        with_precedence("rpo", &self.precedence)
+    }
+}
+
+// wreath product ordering: words are split into syllables by their top-level letters
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Syllable {
+    pub(crate) precedence: Precedence,
+    pub(crate) levels: HashMap<char, usize>,
+}
+
+impl Syllable {
+    // without declared levels every letter is its own level, ranked by precedence;
+    // with them, letters left out sit on level 0
+    fn level(&self, letter: char) -> usize {
+        if self.levels.is_empty() {
+            self.precedence.rank(letter)
+        } else {
+            self.levels.get(&letter).copied().unwrap_or(0)
+        }
+    }
+
+    fn split<'a>(&self, word: &'a [char], level: usize) -> (Vec<char>, Vec<&'a [char]>) {
+        let mut top = vec![];
+        let mut syllables = vec![];
+        let mut start = 0;
+        for (i, &letter) in word.iter().enumerate() {
+            if self.level(letter) == level {
+                top.push(letter);
+                syllables.push(&word[start..i]);
+                start = i + 1;
+            }
+        }
+        syllables.push(&word[start..]);
+        (top, syllables)
+    }
+
+    fn compare_chars(&self, u: &[char], v: &[char]) -> Ordering {
+        if u == v {
+            return Ordering::Equal;
+        }
+        let Some(level) = u.iter().chain(v).map(|&letter| self.level(letter)).max() else {
+            return Ordering::Equal;
+        };
+
+        let (u_top, u_syllables) = self.split(u, level);
+        let (v_top, v_syllables) = self.split(v, level);
+        let ordering = self.precedence.compare_shortlex(&u_top, &v_top);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        for (x, y) in u_syllables.into_iter().zip(v_syllables) {
+            let ordering = self.compare_chars(x, y);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl ReductionOrder for Syllable {
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering> {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        Some(self.compare_chars(&a, &b))
+    }
+
+    fn declaration(&self) -> String {
+        let head = with_precedence("syllable", &self.precedence);
+        if self.levels.is_empty() {
+            head
+        } else {
+            format!("{head} {}", letter_values("levels", &self.levels))
+        }
+    }
+}
+
+fn parse_values(tokens: &[&str], symbols: &Symbols) -> Result<HashMap<char, usize>, String> {
+    let mut values = HashMap::new();
+    for token in tokens {
+        let Some((letter, value)) = token.split_once('=') else {
+            return Err(format!("expected `letter=number`, got `{token}`"));
+        };
+        let value = value
+            .parse::<usize>()
+            .map_err(|e| format!("bad number in `{token}`: {e}"))?;
+        values.insert(symbols.symbol(letter)?, value);
+    }
+    Ok(values)
+}
+
+// `shortlex c > b > a`, `wtlex c > b > a weights a=1 b=1 c=2`, `rpo c > b > a`,
+// `syllable c > b > a levels a=0 b=0 c=1`, `weight weights a=1 b=2`
+pub(crate) fn parse_ordering(declaration: &str, symbols: &Symbols) -> Result<Box<dyn ReductionOrder>, String> {
+    let tokens: Vec<&str> = declaration.split_whitespace().collect();
+    let Some((&kind, rest)) = tokens.split_first() else {
+        return Err("empty ordering declaration".to_string());
+    };
+
+    let values_at = rest
+        .iter()
+        .position(|&token| token == "weights" || token == "levels")
+        .unwrap_or(rest.len());
+    let (chain, values) = rest.split_at(values_at);
+
+    let mut letters = vec![];
+    for (i, token) in chain.iter().enumerate() {
+        if i % 2 == 1 {
+            if *token != ">" {
+                return Err(format!("expected `>` in precedence, got `{token}`"));
+            }
+        } else {
+            let letter = symbols.symbol(token)?;
+            if letters.contains(&letter) {
+                return Err(format!("letter `{token}` appears twice in precedence"));
+            }
+            letters.push(letter);
+        }
+    }
+    if chain.len() % 2 == 0 && !chain.is_empty() {
+        return Err("precedence must not end with `>`".to_string());
+    }
+    let precedence = Precedence::new(letters).with_alphabet(symbols.alphabet());
+
+    let values_kind = values.first().copied();
+    let values = if values.is_empty() {
+        HashMap::new()
+    } else {
+        parse_values(&values[1..], symbols)?
+    };
+
+    match (kind, values_kind) {
+        ("shortlex", None) => Ok(Box::new(Shortlex { precedence })),
+        ("wtlex", None | Some("weights")) => Ok(Box::new(WeightedLex {
+            precedence,
+            weights: values,
+        })),
+        ("weight", None | Some("weights")) if chain.is_empty() => Ok(Box::new(Weight { weights: values })),
+        ("weight", None | Some("weights")) => Err("weight ordering takes no precedence".to_string()),
+        ("rpo" | "recursive", None) => Ok(Box::new(RecursivePath { precedence })),
+        ("syllable" | "wreath", None | Some("levels")) => Ok(Box::new(Syllable {
+            precedence,
+            levels: values,
+        })),
+        ("shortlex" | "wtlex" | "weight" | "rpo" | "recursive" | "syllable" | "wreath", Some(other)) => {
+            Err(format!("`{other}` is not allowed for {kind} ordering"))
+        }
+        _ => Err(format!("unknown ordering `{kind}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orderings_round_trip_through_their_declarations() {
+        for declaration in [
+            "shortlex c > b > a",
+            "wtlex b > a weights a=1 b=2",
+            "rpo b > a",
+            "syllable b > a levels a=0 b=1",
+            "weight weights a=1 b=2",
+        ] {
+            assert_eq!(parse_ordering(declaration, &Symbols::default()).unwrap().declaration(), declaration);
+        }
+        assert!(parse_ordering("shortlex a > a", &Symbols::default()).is_err());
+    }
+
+    #[test]
+    fn weight_leaves_equal_weights_unoriented() {
+        let ordering = parse_ordering("weight weights a=1 b=1", &Symbols::default()).unwrap();
+        assert_eq!(ordering.compare("ab", "ba"), None);
+        assert!(ordering.ground_greater("ba", "ab"));
+        assert!(ordering.greater("aab", "ba"));
+    }
+
+    #[test]
+    fn undeclared_letters_follow_the_alphabet() {
+        let symbols = Symbols::new(vec!["x1".to_string(), "b".to_string(), "a".to_string()]).unwrap();
+        let x1 = symbols.encode("x1").unwrap();
+        let ordering = parse_ordering("shortlex", &symbols).unwrap();
+        assert!(ordering.greater(&x1, "b"));
+        assert!(ordering.greater("b", "a"));
+
+        let ordering = parse_ordering("shortlex a > x1", &symbols).unwrap();
+        assert!(ordering.greater("a", &x1));
+        assert!(ordering.greater(&x1, "b"));
+        assert_eq!(symbols.decode(&ordering.declaration()), "shortlex a > x1");
+    }
+
+    #[test]
+    fn syllable_ranks_undeclared_letters_below_the_precedence() {
+        let ordering = parse_ordering("syllable c > b", &Symbols::default()).unwrap();
+        assert!(ordering.greater("c", "aaaa"));
+        assert!(ordering.greater("b", "aaaa"));
+        assert!(ordering.greater("bcb", "cbb"));
+
+        let ordering = parse_ordering("syllable levels b=1", &Symbols::default()).unwrap();
+        assert!(ordering.greater("b", "aacc"));
+        assert!(ordering.greater("ab", "ba"));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::utils::interreduction::Reduction;
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::srs_file::{parse_rules, ParseError};
+use crate::utils::symbols::Symbols;
+use std::collections::HashMap;
+use std::fs;
+
+const SOURCE: &str = "# completion of ";
+const CHECKPOINT: &str = "# checkpoint: round ";
+const FINISHED: &str = "# finished after round ";
+const REMOVED: &str = "# removed ";
+
+type Rule = (String, String);
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CompletionState {
+    pub(crate) source: String,
+    pub(crate) round: usize,
+    pub(crate) finished: bool,
+    pub(crate) provenance: HashMap<Rule, String>,
+    pub(crate) removed: Vec<String>,
+}
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+impl CompletionState {
+    pub(crate) fn origin(&self, rule: &Rule) -> &str {
+        self.provenance.get(rule).map_or("input", String::as_str)
+    }
+
+    pub(crate) fn added(&mut self, rule: Rule, word: &str, normal_forms: &[String]) {
+        let normal_forms: Vec<&str> = normal_forms.iter().map(|form| show(form)).collect();
+        let origin = format!(
+            "round {}: critical word {}, normal forms {}",
+            self.round,
+            show(word),
+            normal_forms.join(", ")
+        );
+        self.provenance.insert(rule, origin);
+    }
+
+    // carries the provenance through an interreduction log
+    pub(crate) fn reduced(&mut self, log: &[Reduction]) {
+        for reduction in log {
+            let rule = reduction.rule().clone();
+            let origin = self.provenance.remove(&rule).unwrap_or_else(|| "input".to_string());
+            let line = RulesAddition::rule_line(&rule.0, &rule.1);
+            match reduction.result() {
+                None => self.removed.push(format!(
+                    "{REMOVED}in round {}: {line} ({origin}): {}",
+                    self.round,
+                    reduction.justification()
+                )),
+                Some(result) => {
+                    let origin = format!(
+                        "round {}: {} {line} ({origin}): {}",
+                        self.round,
+                        reduction.action(),
+                        reduction.justification()
+                    );
+                    self.provenance.insert(result, origin);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn to_file(&self, rules_addition: &RulesAddition) -> String {
+        let mut lines = vec![format!("{SOURCE}{}", self.source)];
+        if self.finished {
+            lines.push(format!("{FINISHED}{}", self.round));
+        } else {
+            lines.push(format!("{CHECKPOINT}{}", self.round));
+        }
+        lines.push(format!("ordering: {}", rules_addition.ordering.declaration()));
+        for (left, right) in rules_addition.srs.left_rules.iter().zip(&rules_addition.srs.right_rules) {
+            let rule = (left.clone(), right.clone());
+            lines.push(format!(
+                "{} # {}",
+                RulesAddition::rule_line(left, right),
+                self.origin(&rule)
+            ));
+        }
+        for equation in &rules_addition.srs.equations {
+            lines.push(format!(
+                "{} # {}",
+                RulesAddition::equation_line(&equation.0, &equation.1),
+                self.origin(equation)
+            ));
+        }
+        lines.extend(self.removed.iter().cloned());
+        rules_addition.decode(&lines.join("\n")) + "\n"
+    }
+
+    // written next to the target and renamed, so an interrupted write keeps the last checkpoint
+    pub(crate) fn save(&self, rules_addition: &RulesAddition, path: &str) -> std::io::Result<()> {
+        let temporary = format!("{path}.tmp");
+        fs::write(&temporary, self.to_file(rules_addition))?;
+        fs::rename(&temporary, path)
+    }
+
+    // the rules themselves are read by read_raw_rules, this recovers the comments
+    pub(crate) fn parse(path: &str, source: &str, symbols: &Symbols) -> Result<Self, ParseError> {
+        let mut state = Self::new(path);
+        for (number, line) in source.lines().enumerate() {
+            let number = number + 1;
+            if let Some(source) = line.strip_prefix(SOURCE) {
+                state.source = source.to_string();
+            } else if line.starts_with(CHECKPOINT) {
+                state.round = parse_round(path, number, line, CHECKPOINT)?;
+            } else if line.starts_with(FINISHED) {
+                state.round = parse_round(path, number, line, FINISHED)?;
+                state.finished = true;
+            } else if line.starts_with(REMOVED) {
+                state.removed.push(line.to_string());
+            } else if let Some((rule, origin)) = line.split_once(" # ")
+                && let mut file = parse_rules(path, rule, symbols).map_err(|e| ParseError { line: number, ..e })?
+                && let Some(rule) = file.rules.pop().or(file.equations.pop())
+            {
+                state.provenance.insert(rule, origin.to_string());
+            }
+        }
+        Ok(state)
+    }
+}
+
+fn parse_round(path: &str, number: usize, line: &str, prefix: &str) -> Result<usize, ParseError> {
+    let round = line[prefix.len()..].trim();
+    round.parse().map_err(|e| {
+        ParseError::new(path, number, line, prefix.len(), format!("expected round number, got `{round}`: {e}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn checkpoint_round_trips_with_multi_character_letters() {
+        let rules_addition = system(&["b", "x1"], "x1x1 -> b\nbx1 -> b\n");
+        let added = (rules_addition.srs.left_rules[0].clone(), rules_addition.srs.right_rules[0].clone());
+        let input = (rules_addition.srs.left_rules[1].clone(), rules_addition.srs.right_rules[1].clone());
+
+        let mut state = CompletionState::new("rules.txt");
+        state.round = 3;
+        state.provenance.insert(added, "round 2: critical word x1x1x1, normal forms bx1, b".to_string());
+        state.provenance.insert(input, "input".to_string());
+        state.removed.push(format!("{REMOVED}in round 1: b -> b (input): test"));
+
+        let text = state.to_file(&rules_addition);
+        let parsed = CompletionState::parse("out.txt", &text, &rules_addition.srs.symbols).unwrap();
+        assert_eq!(parsed.source, "rules.txt");
+        assert_eq!((parsed.round, parsed.finished), (3, false));
+        assert_eq!(parsed.provenance, state.provenance);
+        assert_eq!(parsed.removed, state.removed);
+        assert_eq!(parsed.to_file(&rules_addition), text);
+    }
+
+    #[test]
+    fn diagnostics_name_the_checkpoint_line() {
+        let symbols = Symbols::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let text = "# completion of rules.txt\n# checkpoint: round 1\nab -> a # input\naz -> a # input\n";
+        let error = CompletionState::parse("out.txt", text, &symbols).unwrap_err();
+        assert_eq!((error.line, error.column), (4, 2));
+
+        let text = "# completion of rules.txt\n# checkpoint: round one\n";
+        let error = CompletionState::parse("out.txt", text, &symbols).unwrap_err();
+        assert_eq!((error.line, error.column), (2, 21));
+        assert!(error.message.starts_with("expected round number, got `one`"));
+    }
+}
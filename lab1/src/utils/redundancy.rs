@@ -0,0 +1,225 @@
+use crate::cli::{csv_writer, Format, Options};
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::symbols::Symbols;
+use crate::utils::word_problem::{EqualityProof, WordProblem, WordProblemLimits};
+use log::info;
+use std::fmt;
+
+type Rule = (String, String);
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+#[derive(Debug)]
+pub(crate) enum Verdict {
+    // the sides are equal in the congruence of the other rules
+    Redundant(EqualityProof),
+    // the other rules cannot prove it
+    Independent(String),
+    Unknown(usize),
+}
+
+impl Verdict {
+    fn new(result: WordProblem) -> Self {
+        match result {
+            WordProblem::Equal(proof) => Verdict::Redundant(proof),
+            WordProblem::NotEqual {
+                u_normal_form,
+                v_normal_form,
+            } => Verdict::Independent(format!(
+                "normal forms {} and {} differ",
+                show(&u_normal_form),
+                show(&v_normal_form)
+            )),
+            WordProblem::FiniteClass { word, class_size } => {
+                Verdict::Independent(format!("the class of {} has only {class_size} words", show(&word)))
+            }
+            WordProblem::Separated(separation) => Verdict::Independent(separation.to_string()),
+            WordProblem::Unknown { states } => Verdict::Unknown(states),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Verdict::Redundant(_) => "redundant",
+            Verdict::Independent(_) => "independent",
+            Verdict::Unknown(_) => "unknown",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            Verdict::Redundant(proof) => proof.to_string(),
+            Verdict::Independent(reason) => reason.clone(),
+            Verdict::Unknown(states) => format!("not decided within {states} words"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RedundancyReport {
+    pub(crate) rules: Vec<Rule>,
+    // each rule against all the others
+    pub(crate) verdicts: Vec<Verdict>,
+    // indexes of the rules in the suggested subset
+    pub(crate) kept: Vec<usize>,
+    // each dropped rule against the subset, the check that the congruence is unchanged
+    pub(crate) checks: Vec<(usize, Verdict)>,
+}
+
+impl RedundancyReport {
+    pub(crate) fn congruence_unchanged(&self) -> bool {
+        self.checks.iter().all(|(_, verdict)| matches!(verdict, Verdict::Redundant(_)))
+    }
+}
+
+impl fmt::Display for RedundancyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (rule, verdict) in self.rules.iter().zip(&self.verdicts) {
+            writeln!(
+                f,
+                "{}: {}, {}",
+                RulesAddition::rule_line(&rule.0, &rule.1),
+                verdict.name(),
+                verdict.detail()
+            )?;
+        }
+
+        writeln!(f, "Minimal equivalent subset ({} of {} rules):", self.kept.len(), self.rules.len())?;
+        for &i in &self.kept {
+            writeln!(f, "{}", RulesAddition::rule_line(&self.rules[i].0, &self.rules[i].1))?;
+        }
+
+        if self.congruence_unchanged() {
+            writeln!(f, "Congruence unchanged, every dropped rule follows from the subset:")?;
+        } else {
+            writeln!(f, "Could not confirm that the congruence is unchanged:")?;
+        }
+        for (i, verdict) in &self.checks {
+            writeln!(
+                f,
+                "{}: {}",
+                RulesAddition::rule_line(&self.rules[*i].0, &self.rules[*i].1),
+                verdict.detail()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl RulesAddition {
+    fn prove_from(&mut self, rules: &[Rule], indexes: &[usize], rule: &Rule, limits: &WordProblemLimits) -> Verdict {
+        self.set_rules(indexes.iter().map(|&i| rules[i].clone()).collect());
+        Verdict::new(self.decide_equal(&rule.0, &rule.1, limits))
+    }
+
+    // equations of the file stay in every subset, only rules are candidates for removal
+    pub(crate) fn find_redundant(&mut self, rules: Vec<Rule>, limits: &WordProblemLimits) -> RedundancyReport {
+        let all: Vec<usize> = (0..rules.len()).collect();
+        let verdicts = all
+            .iter()
+            .map(|&i| {
+                let others: Vec<usize> = all.iter().copied().filter(|&j| j != i).collect();
+                let verdict = self.prove_from(&rules, &others, &rules[i], limits);
+                let rule = Self::rule_line(&rules[i].0, &rules[i].1);
+                info!("{} is {}", self.decode(&rule), verdict.name());
+                verdict
+            })
+            .collect();
+
+        // longer rules are more often consequences of shorter ones, so they are dropped first
+        let mut order = all.clone();
+        order.sort_by_key(|&i| std::cmp::Reverse((rules[i].0.len() + rules[i].1.len(), i)));
+        let mut kept = all;
+        for i in order {
+            let others: Vec<usize> = kept.iter().copied().filter(|&j| j != i).collect();
+            if let Verdict::Redundant(_) = self.prove_from(&rules, &others, &rules[i], limits) {
+                info!("Dropped {}", self.decode(&Self::rule_line(&rules[i].0, &rules[i].1)));
+                kept = others;
+            }
+        }
+
+        let kept_rules = kept.clone();
+        let checks = (0..rules.len())
+            .filter(|i| !kept.contains(i))
+            .map(|i| (i, self.prove_from(&rules, &kept_rules, &rules[i], limits)))
+            .collect();
+        self.set_rules(rules.clone());
+        RedundancyReport {
+            rules,
+            verdicts,
+            kept,
+            checks,
+        }
+    }
+}
+
+fn write_report(report: &RedundancyReport, symbols: &Symbols) -> csv::Result<()> {
+    let mut writer = csv_writer();
+    writer.write_record(["rule", "verdict", "detail", "kept", "proof_from_subset"])?;
+    for (i, (rule, verdict)) in report.rules.iter().zip(&report.verdicts).enumerate() {
+        let check = report
+            .checks
+            .iter()
+            .find(|(j, _)| *j == i)
+            .map(|(_, verdict)| symbols.decode(&verdict.detail()))
+            .unwrap_or_default();
+        writer.write_record([
+            symbols.decode(&RulesAddition::rule_line(&rule.0, &rule.1)),
+            verdict.name().to_string(),
+            symbols.decode(&verdict.detail()),
+            if report.kept.contains(&i) { "yes" } else { "no" }.to_string(),
+            check,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn start_redundancy_report(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    let Some(rules) = rules_addition.read_raw_rules(&options.rules_path) else {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    };
+    info!("Rules read");
+
+    let report = rules_addition.find_redundant(rules, &options.word_problem);
+    match options.format {
+        Format::Csv => {
+            if let Err(e) = write_report(&report, &rules_addition.srs.symbols) {
+                return Err(format!("Error while writing csv {e}"));
+            }
+        }
+        Format::Text => print!("{}", rules_addition.decode(&report.to_string())),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn derivable_rules_are_dropped_and_necessary_ones_kept() {
+        let mut rules_addition = system(&["a", "b"], "");
+        let rules = vec![
+            ("aa".to_string(), String::new()),
+            ("aaa".to_string(), "a".to_string()),
+            ("bb".to_string(), "b".to_string()),
+        ];
+        let report = rules_addition.find_redundant(rules, &WordProblemLimits::default());
+        assert!(matches!(
+            report.verdicts[..],
+            [Verdict::Independent(_), Verdict::Redundant(_), Verdict::Independent(_)]
+        ));
+        assert_eq!(report.kept, [0, 2]);
+        assert!(report.congruence_unchanged());
+        assert_eq!(rules_addition.srs.left_rules, ["aa", "aaa", "bb"]);
+    }
+}
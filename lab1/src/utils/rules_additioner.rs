@@ -1,168 +1,265 @@
+use crate::cli::Options;
+use crate::utils::loops::{LoopWitness, RewriteStep};
+use crate::utils::orderings::{Precedence, ReductionOrder, Shortlex};
+use crate::utils::provenance::CompletionState;
+use crate::utils::shortlex::ShortlexWords;
+use crate::utils::srs::{occurrences, Srs};
+use crate::utils::srs_file::COMPLETED_RULES_PATH;
 use log::{error, info, warn};
-use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::thread;
+
+// words of one length are checked in chunks of this many by worker threads, each with
+// a stack deep enough for get_normal_forms
+const CHUNK_SIZE: usize = 64;
+const WORKER_STACK_SIZE: usize = 256 * 1024 * 1024;
 
 #[derive(Debug)]
-struct RulesAddition {
-    letters: HashMap<usize, String>,
-    right_rules: Vec<String>,
-    left_rules: Vec<String>,
-    cur_len: usize,
-    max_len: usize,
-    alphabet_len: usize,
-    error: bool,
+pub(crate) struct RulesAddition {
+    pub(crate) letters: HashMap<usize, String>,
+    pub(crate) srs: Srs,
+    pub(crate) alphabet_len: usize,
+    pub(crate) error: bool,
     history: HashMap<String, Vec<String>>,
+    // lengths whose words all have one normal form, with the rules that decided it
+    checked: HashMap<usize, Vec<(String, String)>>,
+    pub(crate) ordering: Box<dyn ReductionOrder>,
 }
 
 impl RulesAddition {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             letters: HashMap::new(),
-            right_rules: vec![],
-            left_rules: vec![],
-            cur_len: 0,
-            max_len: 0,
+            srs: Srs::default(),
             alphabet_len: 0,
             error: false,
             history: HashMap::new(),
+            checked: HashMap::new(),
+            ordering: Box::new(Shortlex::default()),
         }
     }
 
-    fn read_letters(&mut self) {
-        info!("Trying to open file data/alphabet.txt");
-        let file = match File::open("data/alphabet.txt") {
-            Ok(f) => {
-                info!("File data/alphabet.txt opened successfully");
-                f
+    pub(crate) fn read_letters(&mut self, path: &str) {
+        match Srs::read_alphabet(path) {
+            Ok(srs) => {
+                self.srs = srs;
+                self.use_symbols();
             }
             Err(e) => {
-                error!("Error while open file {e}");
+                error!("{e}");
                 self.error = true;
-                return;
             }
-        };
+        }
+    }
 
-        let reader = BufReader::new(file);
-        let mut letters = vec![];
-        for (i, line) in reader.lines().enumerate() {
-            if i == 0 {
-                match line.unwrap().parse::<usize>() {
-                    Ok(n) => self.max_len = n,
-                    Err(e) => {
-                        self.error = true;
-                        error!("Parse max len with error: {e}");
-                        return;
-                    }
-                }
-            } else {
-                let letter = line.unwrap();
-                letters.push(letter);
-            }
+    // the letter table and the default ordering follow the symbols of the system
+    fn use_symbols(&mut self) {
+        self.letters.clear();
+        for (i, &symbol) in self.srs.symbols.alphabet().iter().enumerate() {
+            self.letters.insert(i, symbol.to_string());
         }
 
-        letters.sort_by(|a, b| {
-            if a.len() != b.len() {
-                a.len().cmp(&b.len())
-            } else {
-                a.cmp(b)
-            }
-        });
+        self.alphabet_len = self.letters.len();
+        let precedence = Precedence::default().with_alphabet(self.srs.symbols.alphabet());
+        self.ordering = Box::new(Shortlex { precedence });
+    }
+
+    pub(crate) fn alphabet(&self) -> Vec<String> {
+        (0..self.alphabet_len).map(|i| self.letters[&i].clone()).collect()
+    }
+
+    // shortlex over the alphabet order, which for multi-character letters
+    // differs from the order of their symbols
+    pub(crate) fn compare_shortlex(&self, a: &str, b: &str) -> Ordering {
+        let rank = |symbol: char| self.srs.symbols.rank(symbol);
+        a.len()
+            .cmp(&b.len())
+            .then_with(|| a.chars().map(rank).cmp(b.chars().map(rank)))
+    }
+
+    // a word typed by the user, as symbols
+    pub(crate) fn encode(&self, text: &str) -> Result<String, String> {
+        self.srs.encode(text)
+    }
+
+    // words and messages about them with letter names in place of symbols
+    pub(crate) fn decode(&self, text: &str) -> String {
+        self.srs.symbols.decode(text)
+    }
+
+    pub(crate) fn read_rules(&mut self, path: &str) {
+        let Some(rules) = self.read_raw_rules(path) else {
+            return;
+        };
+        self.srs.left_rules.clear();
+        self.srs.right_rules.clear();
 
-        for (i, letter) in letters.into_iter().enumerate() {
-            self.letters.insert(i, letter);
+        // input equations are oriented like rules when the ordering can do it
+        let equations = std::mem::take(&mut self.srs.equations);
+        for (left, right) in rules.into_iter().chain(equations) {
+            self.add_rules(&left, &right);
         }
 
-        self.alphabet_len = self.letters.len();
+        info!("--------------------------------");
+        let left_rules: Vec<String> = self.srs.left_rules.iter().map(|left| self.decode(left)).collect();
+        let right_rules: Vec<String> = self.srs.right_rules.iter().map(|right| self.decode(right)).collect();
+        info!("left rules: {left_rules:?}");
+        info!("right rules: {right_rules:?}");
+        info!("--------------------------------");
     }
 
-    fn read_rules(&mut self) {
-        info!("Trying to open file data/rules.txt");
-        let file = match File::open("data/rules.txt") {
-            Ok(f) => {
-                info!("File data/rules.txt opened successfully");
-                f
-            }
+    pub(crate) fn read_raw_rules(&mut self, path: &str) -> Option<Vec<(String, String)>> {
+        let symbols = self.srs.symbols.clone();
+        let ordering = match self.srs.read_rules(path) {
+            Ok(ordering) => ordering,
             Err(e) => {
-                error!("Error while open file {e}");
+                error!("{e}");
                 self.error = true;
-                return;
+                return None;
             }
         };
+        if self.srs.symbols != symbols {
+            self.use_symbols();
+        }
+        if let Some(ordering) = ordering {
+            info!("Using ordering {}", self.decode(&ordering.declaration()));
+            self.ordering = ordering;
+        }
+        Some(self.srs.rules())
+    }
 
-        let reader = BufReader::new(file);
-        self.left_rules.clear();
-        self.right_rules.clear();
+    pub(crate) fn set_rules(&mut self, rules: Vec<(String, String)>) {
+        self.srs.set_rules(rules);
+    }
 
-        for line in reader.lines() {
-            let rule = line.unwrap();
-            if rule.is_empty() {
-                continue;
+    fn add_rules(&mut self, left: &str, right: &str) {
+        let (normsl, normsr) = match (
+            self.get_normal_forms(left, vec![], &mut HashMap::new()),
+            self.get_normal_forms(right, vec![], &mut HashMap::new()),
+        ) {
+            (Ok(normsl), Ok(normsr)) => (normsl, normsr),
+            (Err(witness), _) | (_, Err(witness)) => {
+                error!("Rewriting does not terminate: {}", self.decode(&witness.to_string()));
+                self.error = true;
+                return;
             }
-            if let Some(index) = rule.find(" -> ") {
-                let left = &rule[..index];
-                let mut right = &rule[index + 4..];
-                if right == "." {
-                    right = ""
-                }
-                self.add_rules(left, right);
+        };
+
 
+        if normsr[0] != normsl[0] {
+            match self.orient(&normsl[0], &normsr[0]) {
+                Some((left, right)) => {
+                    self.srs.left_rules.push(left);
+                    self.srs.right_rules.push(right);
+                }
+                None => self.add_equation(&normsl[0], &normsr[0]),
             }
         }
-
-        info!("--------------------------------");
-        info!("left rules: {:?}", self.left_rules);
-        info!("right rules: {:?}", self.right_rules);
-        info!("--------------------------------");
     }
 
-    fn add_rules(&mut self, left: &str, right: &str) {
-        if self.left_rules.is_empty() {
-            self.left_rules.push(left.to_string());
-            self.right_rules.push(right.to_string());
-            return;
+    pub(crate) fn add_equation(&mut self, u: &str, v: &str) {
+        let known = self
+            .srs
+            .equations
+            .iter()
+            .any(|(left, right)| (left == u && right == v) || (left == v && right == u));
+        if !known {
+            info!(
+                "Ordering {} cannot orient {}, kept as an equation",
+                self.decode(&self.ordering.declaration()),
+                self.decode(&Self::equation_line(u, v))
+            );
+            self.srs.equations.push((u.to_string(), v.to_string()));
         }
+    }
 
-        let normsl =self.get_normal_forms(left, vec![], &mut HashMap::new());
-        let normsr = self.get_normal_forms(right, vec![], &mut HashMap::new());
-
+    // equations turned the way they are applied, for code that needs plain rules
+    pub(crate) fn oriented_equations(&self) -> Vec<(String, String)> {
+        self.srs.equations
+            .iter()
+            .map(|(u, v)| {
+                if self.ordering.ground_greater(u, v) {
+                    (u.clone(), v.clone())
+                } else {
+                    (v.clone(), u.clone())
+                }
+            })
+            .collect()
+    }
 
-        if normsr[0] != normsl[0] {
-            if self.llo(&normsl[0], &normsr[0]) {
-                self.left_rules.push(normsr[0].clone());
-                self.right_rules.push(normsl[0].clone());
-            } else {
-                self.left_rules.push(normsl[0].clone());
-                self.right_rules.push(normsr[0].clone());
+    // every step with an equation that makes the word smaller, leftmost first
+    pub(crate) fn equation_steps(&self, word: &str) -> Vec<(RewriteStep, String)> {
+        let mut steps = vec![];
+        for (u, v) in &self.srs.equations {
+            for (from, to) in [(u, v), (v, u)] {
+                if from.is_empty() {
+                    continue;
+                }
+                for position in occurrences(word, from) {
+                    let next = Srs::rewrite(word, position, from, to);
+                    if self.ordering.ground_greater(word, &next) {
+                        let step = RewriteStep {
+                            left: from.clone(),
+                            right: to.clone(),
+                            position,
+                        };
+                        steps.push((step, next));
+                    }
+                }
             }
         }
+        steps.sort_by_key(|(step, _)| step.position);
+        steps
     }
 
-    fn get_normal_forms(&mut self, start: &str, mut history: Vec<String>,  cache: &mut HashMap<String, Vec<String>>) -> Vec<String> {
+    pub(crate) fn orient(&self, u: &str, v: &str) -> Option<(String, String)> {
+        if self.ordering.greater(u, v) {
+            Some((u.to_string(), v.to_string()))
+        } else if self.ordering.greater(v, u) {
+            Some((v.to_string(), u.to_string()))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn get_normal_forms(&mut self, start: &str, history: Vec<String>,  cache: &mut HashMap<String, Vec<String>>) -> Result<Vec<String>, LoopWitness> {
+        let mut derivations = std::mem::take(&mut self.history);
+        let normal_forms = self.collect_normal_forms(start, history, cache, &mut derivations);
+        self.history = derivations;
+        normal_forms
+    }
+
+    // every normal form reachable from start; the derivation of each one goes to derivations
+    fn collect_normal_forms(
+        &self,
+        start: &str,
+        mut history: Vec<String>,
+        cache: &mut HashMap<String, Vec<String>>,
+        derivations: &mut HashMap<String, Vec<String>>,
+    ) -> Result<Vec<String>, LoopWitness> {
 
         if let Some(v) = cache.get(start) {
-            return v.clone();
+            return Ok(v.clone());
+        }
+        if let Some(witness) = self.loop_in_history(&history, start) {
+            return Err(witness);
         }
         history.push(start.to_string());
         let mut normal_forms = vec![];
         let mut is_normal_form = true;
 
-        let rules: Vec<(String, String)> = self
-            .left_rules
-            .iter()
-            .cloned()
-            .zip(self.right_rules.iter().cloned())
-            .collect();
-
-        for (left_rule, right_rule) in rules {
-            let indexes = Self::find_terms(start, &left_rule);
+        for (left_rule, right_rule) in self.srs.left_rules.iter().zip(&self.srs.right_rules) {
+            let indexes = occurrences(start, left_rule);
             if !indexes.is_empty() {
                 is_normal_form = false;
                 for index in indexes {
-                    let mut new_start = start.to_string();
-                    new_start.replace_range(index..index + left_rule.len(), &right_rule);
+                    let new_start = Srs::rewrite(start, index, left_rule, right_rule);
                     let new_history = history.clone();
-                    let more_normal_forms = self.get_normal_forms(&new_start, new_history, cache);
+                    let more_normal_forms = self.collect_normal_forms(&new_start, new_history, cache, derivations)?;
 
                     for form in more_normal_forms {
                         if !normal_forms.contains(&form) {
@@ -172,161 +269,500 @@ impl RulesAddition {
                 }
             }
         }
+        for (_, new_start) in self.equation_steps(start) {
+            is_normal_form = false;
+            let forms = self.collect_normal_forms(&new_start, history.clone(), cache, derivations)?;
+            for form in forms {
+                if !normal_forms.contains(&form) {
+                    normal_forms.push(form);
+                }
+            }
+        }
         if is_normal_form {
             normal_forms.push(start.to_string());
-            self.history
+            derivations
                 .insert(history.last().unwrap().clone(), history);
         }
+        cache.insert(start.to_string(), normal_forms.clone());
 
-        normal_forms
+        Ok(normal_forms)
     }
 
-    fn find_terms(string: &str, term: &str) -> Vec<usize> {
-        let mut result = vec![];
-        let mut pos = string.find(term);
-        while let Some(i) = pos {
-            result.push(i);
-            pos = string[i + 1..].find(term).map(|x| x + i + 1);
+    pub(crate) fn normalize(&self, word: &str) -> String {
+        let mut steps = 0;
+        self.normalize_counted(word, &mut steps, usize::MAX)
+            .unwrap_or_else(|| word.to_string())
+    }
+
+    pub(crate) fn normalize_counted(
+        &self,
+        word: &str,
+        steps: &mut usize,
+        max_steps: usize,
+    ) -> Option<String> {
+        let mut current = word.to_string();
+        while let Some(next) = self.rewrite_leftmost(&current) {
+            if *steps >= max_steps {
+                return None;
+            }
+            *steps += 1;
+            current = next;
         }
-        result
+        Some(current)
     }
 
-    fn reduction_rules(&mut self) {
-        let mut size = self.left_rules.len();
-        let mut i = 0usize;
-        while i < size {
-            let starting = self.left_rules[i].clone();
-            let left = self.left_rules[i].clone();
-            let right = self.right_rules[i].clone();
+    pub(crate) fn leftmost_redex(&self, word: &str) -> Option<(usize, usize)> {
+        self.srs.leftmost_redex(word)
+    }
 
-            self.left_rules.remove(i);
-            self.right_rules.remove(i);
+    // rules first, equations only once no rule applies
+    pub(crate) fn rewrite_leftmost(&self, word: &str) -> Option<String> {
+        match self.leftmost_redex(word) {
+            Some(redex) => Some(self.srs.rewrite_redex(word, redex)),
+            None => self.equation_steps(word).into_iter().next().map(|(_, next)| next),
+        }
+    }
 
-            let normal_forms = self.get_normal_forms(&starting, vec![], &mut HashMap::new());
-            if !normal_forms.contains(&right) {
-                self.left_rules.insert(i, left);
-                self.right_rules.insert(i, right);
-                i += 1;
-            } else {
-                size -= 1;
+    pub(crate) fn rule_line(left: &str, right: &str) -> String {
+        if !right.is_empty() {
+            format!("{} -> {}", left, right)
+        } else {
+            format!("{} -> .", left)
+        }
+    }
+
+    // the file's equations, for reports that cover only the rules
+    pub(crate) fn equation_lines(&self) -> Option<String> {
+        if self.srs.equations.is_empty() {
+            return None;
+        }
+        let lines: Vec<String> = self.srs.equations.iter().map(|(u, v)| Self::equation_line(u, v)).collect();
+        Some(lines.join(", "))
+    }
+
+    pub(crate) fn equation_line(u: &str, v: &str) -> String {
+        format!("{} = {}", Self::side(u), Self::side(v))
+    }
+
+    fn side(word: &str) -> &str {
+        if word.is_empty() { "." } else { word }
+    }
+
+    // the rules that can fire while rewriting words of the given length; None if some rule
+    // makes words longer, then any rule can
+    fn rules_up_to(&self, length: usize) -> Option<Vec<(String, String)>> {
+        let mut rules = vec![];
+        for (left, right) in self.srs.left_rules.iter().zip(&self.srs.right_rules) {
+            if right.len() > left.len() || !self.srs.equations.is_empty() {
+                return None;
+            }
+            if left.len() <= length {
+                rules.push((left.clone(), right.clone()));
             }
         }
+        rules.sort();
+        Some(rules)
     }
 
+    // true if every rule makes words shortlex-smaller and no left side contains another.
+    // Then the other successors of the shortlex-first conflicting word have one normal form,
+    // so two redexes with different results cover it, one at each end, and any redex inside
+    // would give a conflict on a shorter factor: only words with an irreducible inside matter
+    fn conflicts_have_irreducible_inside(&self) -> bool {
+        let alphabet = self.alphabet();
+        let key = |word: &str| -> Option<(usize, Vec<usize>)> {
+            let letters = word
+                .chars()
+                .map(|c| alphabet.iter().position(|letter| letter.starts_with(c)))
+                .collect::<Option<Vec<usize>>>()?;
+            Some((letters.len(), letters))
+        };
+        let decreasing = self.srs.left_rules.iter().zip(&self.srs.right_rules).all(|(left, right)| {
+            matches!((key(left), key(right)), (Some(left), Some(right)) if left > right)
+        });
+        let interreduced = self.srs.left_rules.iter().enumerate().all(|(i, left)| {
+            self.srs.left_rules
+                .iter()
+                .enumerate()
+                .all(|(j, other)| i == j || other.is_empty() || !left.contains(other.as_str()))
+        });
+        decreasing && interreduced && self.srs.equations.is_empty()
+    }
+
+    // the first of the words with several normal forms or a looping rewrite.
+    // Workers take chunks of the words in order and skip everything past the best
+    // index found so far, so the answer is the one a sequential scan gives
+    fn first_conflict_index(&self, words: &[String]) -> Option<usize> {
+        let count = words.len();
+        let workers = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(count.div_ceil(CHUNK_SIZE));
+        let next_chunk = AtomicUsize::new(0);
+        let best = AtomicUsize::new(usize::MAX);
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                thread::Builder::new()
+                    .stack_size(WORKER_STACK_SIZE)
+                    .spawn_scoped(scope, || loop {
+                        let start = next_chunk.fetch_add(CHUNK_SIZE, AtomicOrdering::Relaxed);
+                        if start >= count.min(best.load(AtomicOrdering::Relaxed)) {
+                            break;
+                        }
+                        for (i, word) in words.iter().enumerate().take(start + CHUNK_SIZE).skip(start) {
+                            if i >= best.load(AtomicOrdering::Relaxed) {
+                                break;
+                            }
+                            match self.collect_normal_forms(word, vec![], &mut HashMap::new(), &mut HashMap::new()) {
+                                Ok(normal_forms) if normal_forms.len() == 1 => {
+                                    let normal_form = self.decode(show(&normal_forms[0]));
+                                    let shown = self.decode(show(word));
+                                    info!("{shown} norm: {shown} -> {normal_form}");
+                                }
+                                _ => {
+                                    best.fetch_min(i, AtomicOrdering::Relaxed);
+                                    break;
+                                }
+                            }
+                        }
+                    })
+                    .expect("cannot start a worker thread");
+            }
+        });
+
+        let best = best.into_inner();
+        (best != usize::MAX).then_some(best)
+    }
 
-    fn gen_string(&self, number: usize, length: usize) -> String {
-        if length == 0 {
-            return String::new();
+    // the shortlex-first word up to max_len with several normal forms, sorted by the ordering.
+    // A length is skipped if it was checked before with the same rules up to that length
+    pub(crate) fn find_conflict(&mut self) -> Result<Option<(String, Vec<String>)>, LoopWitness> {
+        let automaton = self.normal_form_automaton();
+        let pruned = self.conflicts_have_irreducible_inside();
+        for length in 0..=self.srs.max_len {
+            let rules = self.rules_up_to(length);
+            if rules.is_some() && self.checked.get(&length) == rules.as_ref() {
+                info!("Words of length {length} already checked with the same rules");
+                continue;
+            }
+            let head = if pruned { 1 } else { length };
+            let words: Vec<String> = ShortlexWords::of_length(&automaton, length, head, 1).collect();
+            info!("Checking {} words of length {length}", words.len());
+            let Some(i) = self.first_conflict_index(&words) else {
+                if let Some(rules) = rules {
+                    self.checked.insert(length, rules);
+                }
+                continue;
+            };
+
+            let word = words[i].clone();
+            self.history.clear();
+            let mut normal_forms = self.get_normal_forms(&word, vec![], &mut HashMap::new())?;
+            normal_forms.sort_by(|a, b| {
+                self.ordering
+                    .compare(a, b)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| self.compare_shortlex(a, b))
+            });
+            return Ok(Some((word, normal_forms)));
         }
-        let prev = self.gen_string(number / self.alphabet_len, length - 1);
-        let letter = self.letters.get(&(number % self.alphabet_len)).unwrap();
-        format!("{}{}", prev, letter)
+        Ok(None)
     }
 
-    fn llo(&self, a: &str, b: &str) -> bool {
-        if a.len() != b.len() {
-            a.len() < b.len()
+    fn firing_rule(&self, word: &str) -> Option<usize> {
+        self.srs.left_rules
+            .iter()
+            .position(|left| !left.is_empty() && word.contains(left.as_str()))
+    }
+
+    fn dot_node(&self, word: &str) -> String {
+        let word = self.decode(word);
+        if word.is_empty() {
+            "E".to_string()
+        } else if word.chars().all(|c| c.is_ascii_alphanumeric()) {
+            word
         } else {
-            a < b
+            format!("\"{}\"", word.replace('"', "\\\""))
         }
     }
 
+    pub(crate) fn normal_form_tree_dot(&self, depth: usize, rule_labels: bool) -> String {
+        let mut blocks = vec![];
+        self.tree_blocks("", 0, depth, rule_labels, &mut blocks);
+
+        let mut dot = String::from("digraph G {\n\n");
+        dot.push_str("node [fontsize=7]\nedge [fontsize=7]\n");
+        dot.push_str("node [shape=circle, fixedsize=true, width=0.25, height=0.25, fontsize=5]\n\n");
+        dot.push_str("E [style=filled, fillcolor=lightgreen]\n");
+        dot.push_str(&blocks.join("\n"));
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn tree_blocks(&self, word: &str, length: usize, depth: usize, rule_labels: bool, blocks: &mut Vec<String>) {
+        if length >= depth {
+            return;
+        }
+        let mut block = String::new();
+        let mut children = vec![];
+        for i in 0..self.alphabet_len {
+            let letter = &self.letters[&i];
+            let child = format!("{word}{letter}");
+            match self.firing_rule(&child) {
+                Some(rule_id) if rule_labels => {
+                    let rule = Self::rule_line(&self.srs.left_rules[rule_id], &self.srs.right_rules[rule_id]);
+                    block.push_str(&format!(
+                        "{} [style=filled, fillcolor=red, xlabel=\"{}\"]\n",
+                        self.dot_node(&child),
+                        self.decode(&rule)
+                    ));
+                }
+                Some(_) => {
+                    block.push_str(&format!("{} [style=filled, fillcolor=red]\n", self.dot_node(&child)));
+                }
+                None => {
+                    block.push_str(&format!("{} [style=filled, fillcolor=lightgreen]\n", self.dot_node(&child)));
+                    children.push(child.clone());
+                }
+            }
+            block.push_str(&format!(
+                "{} -> {} [label=\"{}\"]\n",
+                self.dot_node(word),
+                self.dot_node(&child),
+                self.decode(letter)
+            ));
+        }
+        blocks.push(block);
+        for child in children {
+            self.tree_blocks(&child, length + 1, depth, rule_labels, blocks);
+        }
+    }
 }
 
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
 
-pub fn start_rules_additioner() {
-    env_logger::init();
+pub fn start_graph_writer(options: &Options) -> Result<(), String> {
     let mut rules_addition = RulesAddition::new();
-    rules_addition.read_letters();
+    rules_addition.read_letters(&options.alphabet_path);
     if rules_addition.error {
-        return;
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    let Some(rules) = rules_addition.read_raw_rules(&options.rules_path) else {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    };
+    rules_addition.set_rules(rules);
+
+    let depth = options.max_len.unwrap_or(rules_addition.srs.max_len);
+    let path = options.output_path.as_deref().unwrap_or("graph/graph.dot");
+    let dot = rules_addition.normal_form_tree_dot(depth, options.labels);
+    match std::fs::write(path, dot) {
+        Ok(_) => println!("Graph saved to {path}"),
+        Err(e) => return Err(format!("Error while writing to file {e}")),
+    }
+    Ok(())
+}
+
+
+pub fn start_rules_additioner(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
     }
     info!("Letters read");
 
-    rules_addition.read_rules();
+    let output = options.output_path.as_deref().unwrap_or(COMPLETED_RULES_PATH);
+    let source = options.resume_path.as_deref().unwrap_or(&options.rules_path);
+    // a checkpoint is already oriented and interreduced, fresh input goes through add_rules
+    if options.resume_path.is_some() {
+        let Some(rules) = rules_addition.read_raw_rules(source) else {
+            return Err(format!("Cannot read rules {source}"));
+        };
+        rules_addition.set_rules(rules);
+    } else {
+        rules_addition.read_rules(source);
+    }
+    if rules_addition.error {
+        return Err(format!("Cannot read rules {source}"));
+    }
     info!("Rules read");
 
-    rules_addition.reduction_rules();
-
-    let mut cnt = 0;
-    let mut to_add: HashMap<String, String> = HashMap::new();
-
-    while rules_addition.cur_len <= rules_addition.max_len {
-        'new: loop {
-            for i in 0..rules_addition
-                .alphabet_len
-                .pow(rules_addition.cur_len as u32)
-            {
-                let gen_string = rules_addition.gen_string(i, rules_addition.cur_len);
-                rules_addition.history.clear();
-                let normal_forms = rules_addition.get_normal_forms(&gen_string, vec![], &mut HashMap::new());
-                if normal_forms.len() != 1 {
-                    cnt += 1;
-                    warn!("{gen_string} has more, than 1 normal form");
-                    for (key, val) in &rules_addition.history {
-                        info!("{}: {:?}", key, val)
-                    }
-                    let mut sorted_normal_forms = normal_forms.clone();
-                    sorted_normal_forms.sort_by(|a, b| {
-                        if a.len() != b.len() {
-                            a.len().cmp(&b.len())
-                        } else {
-                            a.cmp(&b)
-                        }
-                    });
-                    for pair in sorted_normal_forms.windows(2) {
-                        to_add.insert(pair[1].clone(), pair[0].clone());
-                    }
-                    rules_addition.cur_len = 0;
-                    break 'new;
-                } else {
-                    info!("{} norm: {} -> {}", gen_string, gen_string, normal_forms[0]);
-                }
+    let mut state = match &options.resume_path {
+        Some(path) => {
+            let text = fs::read_to_string(path).unwrap_or_default();
+            match CompletionState::parse(path, &text, &rules_addition.srs.symbols) {
+                Ok(state) => state,
+                Err(e) => return Err(e.to_string()),
             }
+        }
+        None => CompletionState::new(&options.rules_path),
+    };
+    if state.finished {
+        println!("{source} is already finished after round {}", state.round);
+        return Ok(());
+    }
+    if state.round > 0 {
+        info!("Resuming after round {}", state.round);
+    }
 
-            if cnt == 0 {
-                info!("My job finished! Goodbye!");
-                break;
+    loop {
+        let log = match rules_addition.interreduce(options.completion.max_steps) {
+            Ok(log) => log,
+            Err(reason) => {
+                return Err(format!("Interreduction stopped: {}", rules_addition.decode(&reason.to_string())));
             }
+        };
+        state.reduced(&log);
 
-            for (key, val) in &to_add {
-                info!("Added rule {key} -> {val}")
+        let conflict = match rules_addition.find_conflict() {
+            Ok(conflict) => conflict,
+            Err(witness) => {
+                return Err(format!("Rewriting does not terminate: {}", rules_addition.decode(&witness.to_string())));
             }
+        };
+        let Some((word, normal_forms)) = conflict else {
+            info!("My job finished! Goodbye!");
+            state.finished = true;
+            break;
+        };
+        if let Err(e) = state.save(&rules_addition, output) {
+            return Err(format!("Error while writing checkpoint {output}: {e}"));
+        }
 
-            if to_add.is_empty() {
-                break;
-            }
+        state.round += 1;
+        warn!("{} has more, than 1 normal form", rules_addition.decode(show(&word)));
+        for (key, val) in &rules_addition.history {
+            let val: Vec<String> = val.iter().map(|word| rules_addition.decode(show(word))).collect();
+            info!("{}: {:?}", rules_addition.decode(show(key)), val)
+        }
+        for pair in normal_forms.windows(2) {
+            let Some(rule) = rules_addition.orient(&pair[0], &pair[1]) else {
+                rules_addition.add_equation(&pair[0], &pair[1]);
+                state.added((pair[0].clone(), pair[1].clone()), &word, &normal_forms);
+                continue;
+            };
+            info!("Added rule {}", rules_addition.decode(&RulesAddition::rule_line(&rule.0, &rule.1)));
+            rules_addition.srs.left_rules.push(rule.0.clone());
+            rules_addition.srs.right_rules.push(rule.1.clone());
+            state.added(rule, &word, &normal_forms);
+        }
+    }
 
-            let mut file = File::create("data/rules.txt").unwrap();
-            for (left, right) in rules_addition
-                .left_rules
-                .iter()
-                .zip(rules_addition.right_rules.iter())
-            {
-                if !right.is_empty() {
-                    writeln!(file, "{} -> {}", left, right).unwrap();
-                } else {
-                    writeln!(file, "{} -> .", left).unwrap();
-                }
-            }
+    match state.save(&rules_addition, output) {
+        Ok(_) => println!("Rules saved to {output} after {} rounds", state.round),
+        Err(e) => return Err(format!("Error while writing to file {output}: {e}")),
+    }
+    Ok(())
+}
 
-            if let Some((left, right)) = to_add.iter().next() {
-                if !right.is_empty() {
-                    writeln!(file, "{} -> {}", left, right).unwrap();
-                } else {
-                    writeln!(file, "{} -> .", left).unwrap();
-                }
-                info!("{} -> {} written", left, right);
-                rules_addition.read_rules();
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::utils::symbols::Symbols;
+
+    // the rules of `source` as written, without orienting or completing them
+    pub(crate) fn system(letters: &[&str], source: &str) -> RulesAddition {
+        let mut rules_addition = RulesAddition::new();
+        rules_addition.srs.max_len = 4;
+        let letters = letters.iter().map(|letter| letter.to_string()).collect();
+        rules_addition.srs.symbols = Symbols::new(letters).unwrap();
+        rules_addition.use_symbols();
+        if let Some(ordering) = rules_addition.srs.parse_rules("test", source).unwrap() {
+            rules_addition.ordering = ordering;
+        }
+        rules_addition
+    }
 
-                rules_addition.reduction_rules();
+    #[test]
+    fn default_ordering_follows_the_alphabet() {
+        let rules_addition = system(&["x1", "a"], "");
+        let x1 = rules_addition.encode("x1").unwrap();
+        assert_eq!(rules_addition.orient("a", &x1), Some((x1.clone(), "a".to_string())));
+        assert_eq!(rules_addition.compare_shortlex("a", &x1), Ordering::Less);
+    }
 
-                to_add.clear();
-                cnt = 0;
-            } else {
-                break;
+    fn every_word(rules_addition: &RulesAddition, length: usize) -> Vec<String> {
+        let automaton = rules_addition.normal_form_automaton();
+        ShortlexWords::of_length(&automaton, length, length, 0).collect()
+    }
+
+    fn sequential_conflict_index(rules_addition: &RulesAddition, words: &[String]) -> Option<usize> {
+        words.iter().position(|word| {
+            let normal_forms =
+                rules_addition.collect_normal_forms(word, vec![], &mut HashMap::new(), &mut HashMap::new());
+            !matches!(normal_forms, Ok(forms) if forms.len() == 1)
+        })
+    }
+
+    #[test]
+    fn parallel_check_finds_the_first_conflict() {
+        for source in ["cc -> a\ncc -> b\n", "ccccc -> a\nccccc -> b\n", "ba -> ab\n", "a -> b\nb -> a\n"] {
+            let rules_addition = system(&["a", "b", "c"], source);
+            for length in 0..=5 {
+                let words = every_word(&rules_addition, length);
+                assert_eq!(
+                    rules_addition.first_conflict_index(&words),
+                    sequential_conflict_index(&rules_addition, &words),
+                    "{source} at length {length}"
+                );
             }
         }
-        rules_addition.cur_len += 1;
+        let rules_addition = system(&["a", "b", "c"], "ccccc -> a\nccccc -> b\n");
+        assert_eq!(rules_addition.first_conflict_index(&every_word(&rules_addition, 5)), Some(242));
+
+        let mut rules_addition = system(&["a", "b"], "ab -> a\nab -> b\n");
+        let (word, normal_forms) = rules_addition.find_conflict().unwrap().unwrap();
+        assert_eq!((word.as_str(), normal_forms), ("ab", vec!["a".to_string(), "b".to_string()]));
+    }
+
+    #[test]
+    fn pruned_words_keep_the_first_conflict() {
+        let sources = ["ba -> a\ncb -> a\n", "aba -> b\nbab -> a\n", "ca -> ac\ncb -> bc\nba -> ab\n", "bb -> a\nba -> aa\nca -> b\n"];
+        for source in sources {
+            let mut rules_addition = system(&["a", "b", "c"], source);
+            assert!(rules_addition.conflicts_have_irreducible_inside(), "{source}");
+            let expected = (0..=rules_addition.srs.max_len).find_map(|length| {
+                let words = every_word(&rules_addition, length);
+                sequential_conflict_index(&rules_addition, &words).map(|i| words[i].clone())
+            });
+            let conflict = rules_addition.find_conflict().unwrap().map(|(word, _)| word);
+            assert_eq!(conflict, expected, "{source}");
+        }
+        assert!(!system(&["a", "b"], "a -> b\n").conflicts_have_irreducible_inside());
+        assert!(!system(&["a", "b"], "ab -> a\nb -> a\n").conflicts_have_irreducible_inside());
+    }
+
+    #[test]
+    fn checked_lengths_are_skipped_while_their_rules_stay() {
+        let mut rules_addition = system(&["a", "b"], "ba -> ab\n");
+        assert_eq!(rules_addition.find_conflict().unwrap(), None);
+        assert_eq!(rules_addition.checked.len(), 5);
+
+        // a longer rule leaves the shorter lengths checked
+        rules_addition.srs.left_rules.push("bbbb".to_string());
+        rules_addition.srs.right_rules.push("a".to_string());
+        for length in 0..4 {
+            assert_eq!(rules_addition.checked.get(&length), rules_addition.rules_up_to(length).as_ref());
+        }
+        assert_ne!(rules_addition.checked.get(&4), rules_addition.rules_up_to(4).as_ref());
+
+        // a length taken as checked is not searched again
+        let mut rules_addition = system(&["a", "b"], "ab -> a\nab -> b\n");
+        let rules = rules_addition.rules_up_to(2).unwrap();
+        rules_addition.checked.insert(2, rules);
+        let conflict = rules_addition.find_conflict().unwrap().map(|(word, _)| word);
+        assert_eq!(conflict.as_deref(), Some("aab"));
+    }
+
+    #[test]
+    fn graph_labels_are_optional() {
+        let rules_addition = system(&["a", "b"], "bb -> b\n");
+        let plain = rules_addition.normal_form_tree_dot(2, false);
+        let labelled = rules_addition.normal_form_tree_dot(2, true);
+        assert!(plain.contains("bb [style=filled, fillcolor=red]\n"));
+        assert!(!plain.contains("xlabel"));
+        assert!(labelled.contains("bb [style=filled, fillcolor=red, xlabel=\"bb -> b\"]\n"));
     }
 }
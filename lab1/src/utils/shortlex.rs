@@ -0,0 +1,88 @@
+use crate::utils::normal_forms::NormalFormAutomaton;
+use crate::utils::normalizer::Normalizer;
+
+// Words up to max_len in shortlex order of the automaton letters. A word has `head` free
+// letters, then a part that must be irreducible, then `tail` free letters; the trie walk
+// never extends a prefix whose irreducible part already contains a left side.
+// head = tail = 0 gives the normal forms, head = length gives every word.
+#[derive(Debug, Clone)]
+pub(crate) struct ShortlexWords<'a> {
+    automaton: &'a NormalFormAutomaton,
+    head: usize,
+    tail: usize,
+    length: usize,
+    max_len: usize,
+    // prefixes still to extend, with the automaton state of their irreducible part
+    stack: Vec<(String, usize)>,
+}
+
+impl<'a> ShortlexWords<'a> {
+    pub(crate) fn new(automaton: &'a NormalFormAutomaton, max_len: usize, head: usize, tail: usize) -> Self {
+        Self {
+            automaton,
+            head,
+            tail,
+            length: 0,
+            max_len,
+            stack: vec![(String::new(), 0)],
+        }
+    }
+
+    // only the words of exactly this length
+    pub(crate) fn of_length(automaton: &'a NormalFormAutomaton, length: usize, head: usize, tail: usize) -> Self {
+        Self {
+            length,
+            ..Self::new(automaton, length, head, tail)
+        }
+    }
+
+    fn is_free(&self, position: usize) -> bool {
+        let head = self.head.min(self.length);
+        let tail = self.tail.min(self.length - head);
+        position < head || position >= self.length - tail
+    }
+}
+
+impl Iterator for ShortlexWords<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            let Some((word, state)) = self.stack.pop() else {
+                if self.length >= self.max_len {
+                    return None;
+                }
+                self.length += 1;
+                self.stack.push((String::new(), 0));
+                continue;
+            };
+            if word.len() == self.length {
+                return Some(word);
+            }
+
+            // pushed in reverse so the smallest letter comes out first
+            let free = self.is_free(word.len());
+            for (i, letter) in self.automaton.letters.iter().enumerate().rev() {
+                let target = if free {
+                    Some(state)
+                } else {
+                    self.automaton.transitions[state][i]
+                };
+                if let Some(target) = target {
+                    self.stack.push((format!("{word}{letter}"), target));
+                }
+            }
+        }
+    }
+}
+
+// words up to max_len whose inside is irreducible, like the overlap of two left sides
+pub(crate) fn overlap_words(
+    left_rules: &[String],
+    right_rules: &[String],
+    letters: Vec<String>,
+    max_len: usize,
+) -> Vec<String> {
+    let automaton = NormalFormAutomaton::new(&Normalizer::new(left_rules, right_rules), letters);
+    ShortlexWords::new(&automaton, max_len, 1, 1).collect()
+}
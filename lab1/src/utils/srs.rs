@@ -0,0 +1,193 @@
+use crate::utils::orderings::ReductionOrder;
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::shortlex::overlap_words;
+use crate::utils::srs_file::{parse_alphabet, parse_rules};
+use crate::utils::symbols::Symbols;
+use log::{info, warn};
+use std::fs;
+
+// a rewriting system as written in the alphabet and rules files; the completer holds one,
+// the fuzzer and the meta tests start from one
+#[derive(Debug, Default)]
+pub(crate) struct Srs {
+    pub(crate) max_len: usize,
+    pub(crate) symbols: Symbols,
+    pub(crate) left_rules: Vec<String>,
+    pub(crate) right_rules: Vec<String>,
+    // pairs the ordering cannot orient, used only in directions that make words smaller
+    pub(crate) equations: Vec<(String, String)>,
+}
+
+fn read(path: &str) -> Result<String, String> {
+    info!("Trying to open file {path}");
+    let source = fs::read_to_string(path).map_err(|e| format!("Error while open file {path}: {e}"))?;
+    info!("File {path} opened successfully");
+    Ok(source)
+}
+
+// every position where `pattern` occurs in `word`, overlapping occurrences included;
+// the empty word occurs nowhere
+pub(crate) fn occurrences(word: &str, pattern: &str) -> Vec<usize> {
+    let mut positions = vec![];
+    if pattern.is_empty() {
+        return positions;
+    }
+    let mut start = 0;
+    while let Some(pos) = word[start..].find(pattern) {
+        positions.push(start + pos);
+        start += pos + 1;
+    }
+    positions
+}
+
+impl Srs {
+    pub(crate) fn load(rules_path: &str, alphabet_path: &str) -> Result<Self, String> {
+        let mut srs = Self::read_alphabet(alphabet_path)?;
+        srs.read_rules(rules_path)?;
+        Ok(srs)
+    }
+
+    pub(crate) fn read_alphabet(path: &str) -> Result<Self, String> {
+        let alphabet = parse_alphabet(path, &read(path)?).map_err(|e| e.to_string())?;
+        Ok(Self {
+            max_len: alphabet.max_len,
+            symbols: Symbols::new(alphabet.letters).map_err(|e| format!("{path}: {e}"))?,
+            ..Self::default()
+        })
+    }
+
+    // rules replace the ones read before, an alphabet declared in the file replaces the letters.
+    // The ordering declared in the file is for the caller, a system has none of its own
+    pub(crate) fn read_rules(&mut self, path: &str) -> Result<Option<Box<dyn ReductionOrder>>, String> {
+        self.parse_rules(path, &read(path)?)
+    }
+
+    // like read_rules for text already in memory, `path` only names it in diagnostics
+    pub(crate) fn parse_rules(&mut self, path: &str, source: &str) -> Result<Option<Box<dyn ReductionOrder>>, String> {
+        let file = parse_rules(path, source, &self.symbols).map_err(|e| e.to_string())?;
+        if let Some(letters) = file.alphabet {
+            info!("Using alphabet {letters:?} declared in {path}");
+            self.symbols = Symbols::new(letters).map_err(|e| format!("{path}: {e}"))?;
+        }
+        self.set_rules(file.rules);
+        self.equations = file.equations;
+        self.validate().map_err(|e| format!("{path}: {e}"))?;
+        Ok(file.ordering)
+    }
+
+    pub(crate) fn set_rules(&mut self, rules: Vec<(String, String)>) {
+        (self.left_rules, self.right_rules) = rules.into_iter().unzip();
+    }
+
+    pub(crate) fn rules(&self) -> Vec<(String, String)> {
+        self.left_rules.iter().cloned().zip(self.right_rules.iter().cloned()).collect()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, (left, right)) in self.left_rules.iter().zip(&self.right_rules).enumerate() {
+            let line = self.symbols.decode(&RulesAddition::rule_line(left, right));
+            if left.is_empty() {
+                return Err(format!("rule {line} has an empty left side"));
+            }
+            if self.left_rules[..i].iter().zip(&self.right_rules).any(|rule| rule == (left, right)) {
+                warn!("Rule {line} is written twice");
+            }
+        }
+        Ok(())
+    }
+
+    // other rules over the same letters, for a variant of the system
+    pub(crate) fn with_rules(&self, rules: Vec<(String, String)>) -> Result<Self, String> {
+        let mut srs = Self {
+            max_len: self.max_len,
+            symbols: self.symbols.clone(),
+            ..Self::default()
+        };
+        srs.set_rules(rules);
+        srs.validate()?;
+        Ok(srs)
+    }
+
+    pub(crate) fn encode(&self, text: &str) -> Result<String, String> {
+        if text == "." || text == "ε" {
+            return Ok(String::new());
+        }
+        self.symbols
+            .encode(text)
+            .map_err(|(i, message)| format!("{text}: at {i}: {message}"))
+    }
+
+    // every (rule, position) where a left side occurs, overlapping occurrences included
+    pub(crate) fn redexes(&self, word: &str) -> Vec<(usize, usize)> {
+        let mut redexes = vec![];
+        for (rule_id, left) in self.left_rules.iter().enumerate() {
+            redexes.extend(occurrences(word, left).into_iter().map(|position| (rule_id, position)));
+        }
+        redexes
+    }
+
+    // (rule, position) of the redex that starts first, the earlier rule on a tie
+    pub(crate) fn leftmost_redex(&self, word: &str) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize)> = None;
+        for (rule_id, left) in self.left_rules.iter().enumerate() {
+            if left.is_empty() {
+                continue;
+            }
+            if let Some(pos) = word.find(left.as_str())
+                && best.is_none_or(|(_, best_pos)| pos < best_pos)
+            {
+                best = Some((rule_id, pos));
+            }
+        }
+        best
+    }
+
+    // every rewrite step, by a rule, an equation or a rule read backwards, is done here
+    pub(crate) fn rewrite(word: &str, position: usize, left: &str, right: &str) -> String {
+        let mut word = word.to_string();
+        word.replace_range(position..position + left.len(), right);
+        word
+    }
+
+    // the step at a redex found by `redexes` or `leftmost_redex`
+    pub(crate) fn rewrite_redex(&self, word: &str, (rule_id, position): (usize, usize)) -> String {
+        Self::rewrite(word, position, &self.left_rules[rule_id], &self.right_rules[rule_id])
+    }
+
+    // words up to max_len whose inside is irreducible, good start words for random tests
+    pub(crate) fn overlap_words(&self, max_len: usize) -> Vec<String> {
+        let letters = self.symbols.alphabet().iter().map(char::to_string).collect();
+        overlap_words(&self.left_rules, &self.right_rules, letters, max_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn redexes_overlap_and_the_leftmost_one_is_rewritten() {
+        assert_eq!(occurrences("aaaa", "aa"), [0, 1, 2]);
+        assert!(occurrences("aaaa", "").is_empty());
+
+        let rules_addition = system(&["a", "b"], "aa -> b\nba -> ab\n");
+        let srs = &rules_addition.srs;
+        assert_eq!(srs.redexes("baaa"), [(0, 1), (0, 2), (1, 0)]);
+        assert_eq!(srs.leftmost_redex("baaa"), Some((1, 0)));
+        assert_eq!(srs.rewrite_redex("baaa", (0, 2)), "bab");
+        assert_eq!(rules_addition.rewrite_leftmost("baaa").as_deref(), Some("abaa"));
+    }
+
+    #[test]
+    fn variants_keep_the_letters_and_reject_empty_left_sides() {
+        let mut srs = Srs::default();
+        srs.parse_rules("test", "alphabet: x1 b\nx1x1 -> b\n").unwrap();
+        let x1 = srs.encode("x1").unwrap();
+        assert_eq!(srs.rules(), [(x1.repeat(2), "b".to_string())]);
+        let variant = srs.with_rules(vec![(x1.clone(), String::new())]).unwrap();
+        assert_eq!(variant.symbols, srs.symbols);
+        assert!(srs.with_rules(vec![(String::new(), x1)]).is_err());
+        assert!(srs.parse_rules("test", ". -> b\n").unwrap_err().contains("empty left side"));
+    }
+}
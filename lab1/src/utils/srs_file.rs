@@ -0,0 +1,258 @@
+use crate::utils::orderings::{parse_ordering, ReductionOrder};
+use crate::utils::symbols::Symbols;
+use std::fmt;
+use std::ops::Range;
+
+pub(crate) const ALPHABET_PATH: &str = "data/alphabet.txt";
+pub(crate) const RULES_PATH: &str = "data/rules.txt";
+pub(crate) const COMPLETED_RULES_PATH: &str = "data/rules_completed.txt";
+
+const EMPTY_WORDS: [&str; 2] = [".", "ε"];
+const COMMENT: char = '#';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ParseError {
+    pub(crate) path: String,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+    pub(crate) message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(path: &str, line: usize, text: &str, offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            line,
+            column: text[..offset].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.path, self.line, self.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct AlphabetFile {
+    pub(crate) max_len: usize,
+    pub(crate) letters: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RulesFile {
+    pub(crate) alphabet: Option<Vec<String>>,
+    pub(crate) ordering: Option<Box<dyn ReductionOrder>>,
+    pub(crate) rules: Vec<(String, String)>,
+    pub(crate) equations: Vec<(String, String)>,
+}
+
+// numbered lines with comments cut off
+fn lines(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    source.lines().enumerate().map(|(i, line)| {
+        let text = line.split(COMMENT).next().unwrap_or_default();
+        (i + 1, text)
+    })
+}
+
+// whitespace separated tokens with their byte offsets
+fn tokens(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(from), true) => {
+                tokens.push((from, &text[from..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+fn check_letter(letter: &str, letters: &[String]) -> Result<(), String> {
+    if EMPTY_WORDS.contains(&letter) {
+        return Err(format!("`{letter}` denotes the empty word and cannot be a letter"));
+    }
+    // these mark rules, equations and comments in the rules file
+    for marker in ["->", "=", "#"] {
+        if letter.contains(marker) {
+            return Err(format!("`{marker}` is reserved and cannot appear in letter `{letter}`"));
+        }
+    }
+    if letters.iter().any(|known| known == letter) {
+        return Err(format!("letter `{letter}` is declared twice"));
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_alphabet(path: &str, source: &str) -> Result<AlphabetFile, ParseError> {
+    let mut max_len = None;
+    let mut letters: Vec<String> = vec![];
+    let mut last_line = 1;
+    for (number, text) in lines(source) {
+        last_line = number;
+        let Some(&(offset, token)) = tokens(text).first() else {
+            continue;
+        };
+        if let Some(&(extra, _)) = tokens(text).get(1) {
+            return Err(ParseError::new(path, number, text, extra, "expected one entry per line"));
+        }
+        if max_len.is_none() {
+            let value = token.parse::<usize>().map_err(|e| {
+                ParseError::new(path, number, text, offset, format!("expected max length, got `{token}`: {e}"))
+            })?;
+            max_len = Some(value);
+            continue;
+        }
+        check_letter(token, &letters).map_err(|e| ParseError::new(path, number, text, offset, e))?;
+        letters.push(token.to_string());
+    }
+
+    let Some(max_len) = max_len else {
+        return Err(ParseError::new(path, last_line, "", 0, "missing max length"));
+    };
+    if letters.is_empty() {
+        return Err(ParseError::new(path, last_line, "", 0, "alphabet has no letters"));
+    }
+    Ok(AlphabetFile { max_len, letters })
+}
+
+fn parse_side(
+    path: &str,
+    number: usize,
+    text: &str,
+    range: Range<usize>,
+    symbols: &Symbols,
+) -> Result<String, ParseError> {
+    let side = &text[range.clone()];
+    let word = side.trim();
+    let start = range.start + side.len() - side.trim_start().len();
+    let at = |offset: usize, message: String| ParseError::new(path, number, text, offset, message);
+
+    if word.is_empty() {
+        return Err(at(start, "missing rule side, write `.` or `ε` for the empty word".to_string()));
+    }
+    if EMPTY_WORDS.contains(&word) {
+        return Ok(String::new());
+    }
+    if let Some(i) = word.find(char::is_whitespace) {
+        return Err(at(start + i, "whitespace inside a word".to_string()));
+    }
+    symbols.encode(word).map_err(|(i, message)| at(start + i, message))
+}
+
+pub(crate) fn parse_rules(path: &str, source: &str, symbols: &Symbols) -> Result<RulesFile, ParseError> {
+    let mut file = RulesFile::default();
+    let mut symbols = symbols.clone();
+    for (number, text) in lines(source) {
+        let trimmed = text.trim_start();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let offset = text.len() - trimmed.len();
+        let at = |offset: usize, message: String| ParseError::new(path, number, text, offset, message);
+
+        if let Some(declaration) = trimmed.strip_prefix("ordering:") {
+            if file.ordering.is_some() {
+                return Err(at(offset, "ordering is declared twice".to_string()));
+            }
+            let ordering = parse_ordering(declaration, &symbols)
+                .map_err(|e| at(offset + "ordering:".len(), format!("bad ordering: {e}")))?;
+            file.ordering = Some(ordering);
+        } else if let Some(declaration) = trimmed.strip_prefix("alphabet:") {
+            if file.alphabet.is_some() || !file.rules.is_empty() || !file.equations.is_empty() {
+                return Err(at(offset, "alphabet must be declared once, before the rules".to_string()));
+            }
+            let start = offset + "alphabet:".len();
+            let mut letters: Vec<String> = vec![];
+            for (i, letter) in tokens(declaration) {
+                check_letter(letter, &letters).map_err(|e| at(start + i, e))?;
+                letters.push(letter.to_string());
+            }
+            if letters.is_empty() {
+                return Err(at(start, "alphabet has no letters".to_string()));
+            }
+            symbols = Symbols::new(letters.clone()).map_err(|e| at(start, e))?;
+            file.alphabet = Some(letters);
+        } else if !text.contains("->")
+            && let Some(equals) = text.find('=')
+        {
+            if let Some(second) = text[equals + 1..].find('=') {
+                return Err(at(equals + 1 + second, "more than one `=` in an equation".to_string()));
+            }
+            let left = parse_side(path, number, text, 0..equals, &symbols)?;
+            let right = parse_side(path, number, text, equals + 1..text.len(), &symbols)?;
+            if left == right {
+                return Err(at(offset, "both sides of the equation are equal".to_string()));
+            }
+            file.equations.push((left, right));
+        } else {
+            let Some(arrow) = text.find("->") else {
+                let message = "expected a rule `left -> right` or an equation `left = right`";
+                return Err(at(offset, message.to_string()));
+            };
+            if let Some(second) = text[arrow + 2..].find("->") {
+                return Err(at(arrow + 2 + second, "more than one `->` in a rule".to_string()));
+            }
+            let left = parse_side(path, number, text, 0..arrow, &symbols)?;
+            let right = parse_side(path, number, text, arrow + 2..text.len(), &symbols)?;
+            if left == right {
+                return Err(at(offset, "both sides of the rule are equal".to_string()));
+            }
+            file.rules.push((left, right));
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_at(source: &str) -> (usize, usize, String) {
+        let symbols = Symbols::new(vec!["a".into(), "b".into()]).unwrap();
+        let error = parse_rules("r", source, &symbols).unwrap_err();
+        (error.line, error.column, error.message)
+    }
+
+    #[test]
+    fn diagnostics_point_at_the_offending_column() {
+        let (line, column, _) = error_at("ab -> a\n  ac -> b\n");
+        assert_eq!((line, column), (2, 4));
+        assert_eq!(error_at("ab -> a -> b\n").1, 9);
+        assert_eq!(error_at("a b -> a\n").1, 2);
+        assert_eq!(error_at("ab ->\n").2, "missing rule side, write `.` or `ε` for the empty word");
+        assert_eq!(error_at("ab\n").1, 1);
+
+        let error = parse_alphabet("alphabet", "3\na\nb c\n").unwrap_err();
+        assert_eq!(error.to_string(), "alphabet:3:3: expected one entry per line");
+        assert!(parse_alphabet("alphabet", "x\n").is_err());
+    }
+
+    #[test]
+    fn markers_are_not_letters() {
+        for letter in ["#", "x#", "a->b", "a=b", ".", "ε"] {
+            assert!(check_letter(letter, &[]).is_err(), "{letter}");
+        }
+        assert!(check_letter("x1", &[]).is_ok());
+    }
+
+    #[test]
+    fn declared_alphabet_rejects_markers() {
+        let error = parse_rules("r", "alphabet: a = b\n", &Symbols::default()).unwrap_err();
+        assert_eq!((error.line, error.column), (1, 13));
+    }
+
+    #[test]
+    fn rules_and_equations_are_told_apart() {
+        let symbols = Symbols::new(vec!["a".into(), "b".into()]).unwrap();
+        let file = parse_rules("r", "ab -> .\nab = ba # comment\n", &symbols).unwrap();
+        assert_eq!(file.rules, vec![("ab".to_string(), String::new())]);
+        assert_eq!(file.equations, vec![("ab".to_string(), "ba".to_string())]);
+    }
+}
@@ -0,0 +1,170 @@
+// Every letter is one byte inside a word, so byte offsets and lengths count letters.
+// Printable ASCII letters stand for themselves, other letters (`x1`, `a'`, `a⁻¹`, `α`)
+// get control codes that never occur in text. The codes only mean something together
+// with the alphabet that gave them out, so each system keeps its own table.
+const CODES: [u8; 27] = [
+    0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13, 0x14,
+    0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F, 0x7F,
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Symbols {
+    // letter names, shorter first and then by name, which is the alphabet order
+    letters: Vec<String>,
+    symbols: Vec<char>,
+}
+
+impl Symbols {
+    pub(crate) fn new(mut letters: Vec<String>) -> Result<Self, String> {
+        letters.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+        let mut codes = CODES.iter();
+        let mut symbols = vec![];
+        for letter in &letters {
+            let mut chars = letter.chars();
+            if let (Some(c), None) = (chars.next(), chars.next())
+                && c.is_ascii_graphic()
+            {
+                symbols.push(c);
+                continue;
+            }
+            if letter.chars().any(char::is_control) {
+                return Err(format!("letter `{}` contains a control character", letter.escape_debug()));
+            }
+            let Some(&code) = codes.next() else {
+                return Err(format!(
+                    "cannot take `{letter}`: at most {} letters may be multi-character or non-ASCII",
+                    CODES.len()
+                ));
+            };
+            symbols.push(code as char);
+        }
+        Ok(Self { letters, symbols })
+    }
+
+    pub(crate) fn alphabet(&self) -> &[char] {
+        &self.symbols
+    }
+
+    // position in the alphabet order
+    pub(crate) fn rank(&self, symbol: char) -> Option<usize> {
+        self.symbols.iter().position(|&known| known == symbol)
+    }
+
+    // a letter of the alphabet; a printable ASCII character stands for itself
+    pub(crate) fn symbol(&self, letter: &str) -> Result<char, String> {
+        if let Some(i) = self.letters.iter().position(|known| known == letter) {
+            return Ok(self.symbols[i]);
+        }
+        let mut chars = letter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_graphic() => Ok(c),
+            _ => Err(format!("`{letter}` is not a letter of the alphabet")),
+        }
+    }
+
+    // symbols become letter names, so this also takes whole messages about words
+    pub(crate) fn decode(&self, text: &str) -> String {
+        let mut decoded = String::with_capacity(text.len());
+        for c in text.chars() {
+            match self.rank(c) {
+                Some(i) => decoded.push_str(&self.letters[i]),
+                None => decoded.push(c),
+            }
+        }
+        decoded
+    }
+
+    // splits text into letters of the alphabet and returns it as symbols;
+    // on failure gives the byte offset of the problem
+    pub(crate) fn encode(&self, text: &str) -> Result<String, (usize, String)> {
+        if self.letters.is_empty() {
+            return text
+                .char_indices()
+                .map(|(i, c)| self.symbol(c.encode_utf8(&mut [0; 4])).map_err(|e| (i, e)))
+                .collect();
+        }
+
+        // parses[i] counts the ways to split text[..i], capped at 2
+        let mut parses = vec![0u8; text.len() + 1];
+        let mut last: Vec<Option<usize>> = vec![None; text.len() + 1];
+        parses[0] = 1;
+        for i in 0..text.len() {
+            if parses[i] == 0 {
+                continue;
+            }
+            for (letter_id, letter) in self.letters.iter().enumerate() {
+                if text[i..].starts_with(letter.as_str()) {
+                    let end = i + letter.len();
+                    parses[end] = (parses[end] + parses[i]).min(2);
+                    last[end] = Some(letter_id);
+                }
+            }
+        }
+
+        if parses[text.len()] == 0 {
+            let i = (0..text.len()).rev().find(|&i| parses[i] > 0).unwrap_or(0);
+            let c = text[i..].chars().next().unwrap_or_default();
+            return Err((i, format!("`{c}` is not a letter of the alphabet")));
+        }
+        if parses[text.len()] > 1 {
+            return Err((0, format!("`{text}` splits into letters in more than one way")));
+        }
+
+        let mut symbols = vec![];
+        let mut end = text.len();
+        while end > 0 {
+            let letter_id = last[end].expect("the text has a parse");
+            symbols.push(self.symbols[letter_id]);
+            end -= self.letters[letter_id].len();
+        }
+        Ok(symbols.into_iter().rev().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(letters: &[&str]) -> Symbols {
+        Symbols::new(letters.iter().map(|letter| letter.to_string()).collect()).unwrap()
+    }
+
+    #[test]
+    fn words_split_into_letters_once() {
+        let symbols = table(&["x1", "a", "α"]);
+        let alphabet: String = symbols.alphabet().iter().collect();
+        assert_eq!(symbols.decode(&alphabet), "ax1α");
+        let word = symbols.encode("ax1αa").unwrap();
+        assert_eq!(word.len(), 4);
+        assert_eq!(symbols.decode(&word), "ax1αa");
+        assert_eq!(symbols.encode("ax2"), Err((1, "`x` is not a letter of the alphabet".to_string())));
+    }
+
+    #[test]
+    fn ambiguous_words_are_rejected() {
+        let symbols = table(&["a", "b", "ab"]);
+        assert_eq!(
+            symbols.encode("aab"),
+            Err((0, "`aab` splits into letters in more than one way".to_string()))
+        );
+        assert!(symbols.encode("ba").is_ok());
+
+        let symbols = table(&["a", "ab", "ba"]);
+        assert!(symbols.encode("aba").is_err());
+        assert_eq!(symbols.encode("abab").map(|word| word.len()), Ok(2));
+    }
+
+    #[test]
+    fn tables_do_not_depend_on_each_other() {
+        let first = table(&["x1", "y1"]);
+        let second = table(&["y1", "x1"]);
+        assert_eq!(first, second);
+        assert_eq!(table(&["y1"]).encode("y1"), first.encode("x1"));
+        assert_eq!(first.decode(&first.encode("y1x1").unwrap()), "y1x1");
+
+        let letters: Vec<String> = (0..=CODES.len()).map(|i| format!("x{i}")).collect();
+        assert!(Symbols::new(letters[1..].to_vec()).is_ok());
+        assert!(Symbols::new(letters).is_err());
+    }
+}
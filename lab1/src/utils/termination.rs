@@ -0,0 +1,342 @@
+use crate::cli::Options;
+use crate::utils::orderings::{Precedence, RecursivePath, ReductionOrder, Shortlex, WeightedLex};
+use crate::utils::rules_additioner::RulesAddition;
+use log::info;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub(crate) struct TerminationLimits {
+    pub(crate) max_candidates: usize,
+    pub(crate) max_weight: usize,
+}
+
+impl Default for TerminationLimits {
+    fn default() -> Self {
+        Self {
+            max_candidates: 100_000,
+            max_weight: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Certificate {
+    Shortlex(Shortlex),
+    WeightedLex { order: WeightedLex, strict: bool },
+    RecursivePath(RecursivePath),
+}
+
+#[derive(Debug)]
+pub(crate) enum TerminationResult {
+    Proved(Certificate),
+    NotFound { candidates: usize },
+}
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+impl Certificate {
+    pub(crate) fn ordering(&self) -> &dyn ReductionOrder {
+        match self {
+            Certificate::Shortlex(order) => order,
+            Certificate::WeightedLex { order, .. } => order,
+            Certificate::RecursivePath(order) => order,
+        }
+    }
+
+    fn explain_shortlex(precedence: &Precedence, left: &str, right: &str) -> String {
+        let (l, r) = (left.chars().count(), right.chars().count());
+        if l != r {
+            return format!("|{}| = {l} > |{}| = {r}", show(left), show(right));
+        }
+        let (i, (x, y)) = left
+            .chars()
+            .zip(right.chars())
+            .enumerate()
+            .find(|(_, (x, y))| x != y)
+            .expect("rule sides must differ");
+        let sign = if precedence.compare_letters(x, y) == Ordering::Greater { ">" } else { "<" };
+        format!("|{left}| = |{right}| = {l}, first difference at {i}: {x} {sign} {y}")
+    }
+
+    fn explain_rpo(order: &RecursivePath, left: &str, right: &str) -> String {
+        let mut steps = vec![];
+        let (mut u, mut v) = (left.to_string(), right.to_string());
+        loop {
+            if u == v {
+                steps.push(format!("{} = {}", show(&u), show(&v)));
+                break;
+            }
+            let (Some(a), Some(b)) = (u.chars().last(), v.chars().last()) else {
+                steps.push(format!("{} > ε", show(&u)));
+                break;
+            };
+            let u_rest: String = u.chars().take(u.chars().count() - 1).collect();
+            let v_rest: String = v.chars().take(v.chars().count() - 1).collect();
+            match order.precedence.compare_letters(a, b) {
+                Ordering::Equal => {
+                    steps.push(format!("{} vs {}: same last letter {a}", show(&u), show(&v)));
+                    (u, v) = (u_rest, v_rest);
+                }
+                Ordering::Greater => {
+                    steps.push(format!("{} vs {}: {a} > {b}, drop {b}", show(&u), show(&v)));
+                    v = v_rest;
+                }
+                Ordering::Less => {
+                    steps.push(format!("{} vs {}: {a} < {b}, need {} >= {}", show(&u), show(&v), show(&u_rest), show(&v)));
+                    u = u_rest;
+                }
+            }
+        }
+        steps.join("; ")
+    }
+
+    pub(crate) fn explain(&self, left: &str, right: &str) -> String {
+        match self {
+            Certificate::Shortlex(order) => Self::explain_shortlex(&order.precedence, left, right),
+            Certificate::WeightedLex { order, .. } => {
+                let (l, r) = (order.weight(left), order.weight(right));
+                if l > r {
+                    format!("w({}) = {l} > w({}) = {r}", show(left), show(right))
+                } else {
+                    format!(
+                        "w({left}) = w({right}) = {l}, {}",
+                        Self::explain_shortlex(&order.precedence, left, right)
+                    )
+                }
+            }
+            Certificate::RecursivePath(order) => Self::explain_rpo(order, left, right),
+        }
+    }
+}
+
+impl fmt::Display for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Certificate::Shortlex(_) => write!(f, "shortlex ordering")?,
+            Certificate::WeightedLex { strict: true, .. } => write!(f, "weight function")?,
+            Certificate::WeightedLex { strict: false, .. } => write!(f, "weighted shortlex ordering")?,
+            Certificate::RecursivePath(_) => write!(f, "recursive path ordering")?,
+        }
+        write!(f, " (ordering: {})", self.ordering().declaration())
+    }
+}
+
+fn orients_all(order: &dyn ReductionOrder, rules: &[(String, String)]) -> bool {
+    rules.iter().all(|(left, right)| order.greater(left, right))
+}
+
+fn next_permutation(indexes: &mut [usize]) -> bool {
+    let Some(i) = indexes.windows(2).rposition(|pair| pair[0] < pair[1]) else {
+        return false;
+    };
+    let j = indexes.iter().rposition(|&x| x > indexes[i]).unwrap();
+    indexes.swap(i, j);
+    indexes[i + 1..].reverse();
+    true
+}
+
+struct Search<'a> {
+    rules: &'a [(String, String)],
+    letters: Vec<char>,
+    limits: &'a TerminationLimits,
+    candidates: usize,
+}
+
+impl Search<'_> {
+    fn precedences(&self) -> Vec<Precedence> {
+        let mut indexes: Vec<usize> = (0..self.letters.len()).collect();
+        let mut precedences = vec![];
+        loop {
+            precedences.push(Precedence::new(indexes.iter().map(|&i| self.letters[i]).collect()));
+            if precedences.len() >= self.limits.max_candidates || !next_permutation(&mut indexes) {
+                return precedences;
+            }
+        }
+    }
+
+    fn spend(&mut self) -> bool {
+        self.candidates += 1;
+        self.candidates <= self.limits.max_candidates
+    }
+
+    fn shortlex(&mut self) -> Option<Certificate> {
+        for precedence in self.precedences() {
+            if !self.spend() {
+                return None;
+            }
+            let order = Shortlex { precedence };
+            if orients_all(&order, self.rules) {
+                return Some(Certificate::Shortlex(order));
+            }
+        }
+        None
+    }
+
+    fn weights(&mut self) -> Option<Certificate> {
+        let precedences = self.precedences();
+        for max_weight in 1..=self.limits.max_weight {
+            let mut values = vec![0; self.letters.len()];
+            loop {
+                if values.contains(&max_weight) {
+                    let weights: HashMap<char, usize> =
+                        self.letters.iter().copied().zip(values.iter().copied()).collect();
+                    let order = WeightedLex {
+                        precedence: Precedence::new(self.letters.clone()),
+                        weights,
+                    };
+                    if !self.spend() {
+                        return None;
+                    }
+                    let strict = self
+                        .rules
+                        .iter()
+                        .all(|(left, right)| order.weight(left) > order.weight(right));
+                    if strict {
+                        return Some(Certificate::WeightedLex { order, strict });
+                    }
+                    let weak = self
+                        .rules
+                        .iter()
+                        .all(|(left, right)| order.weight(left) >= order.weight(right));
+                    if weak {
+                        for precedence in &precedences {
+                            if !self.spend() {
+                                return None;
+                            }
+                            let order = WeightedLex {
+                                precedence: precedence.clone(),
+                                weights: order.weights.clone(),
+                            };
+                            if orients_all(&order, self.rules) {
+                                return Some(Certificate::WeightedLex { order, strict });
+                            }
+                        }
+                    }
+                }
+
+                let Some(i) = values.iter().position(|&value| value < max_weight) else {
+                    break;
+                };
+                values[i] += 1;
+                values[..i].iter_mut().for_each(|value| *value = 0);
+            }
+        }
+        None
+    }
+
+    fn recursive_path(&mut self) -> Option<Certificate> {
+        for precedence in self.precedences() {
+            if !self.spend() {
+                return None;
+            }
+            let order = RecursivePath { precedence };
+            if orients_all(&order, self.rules) {
+                return Some(Certificate::RecursivePath(order));
+            }
+        }
+        None
+    }
+}
+
+pub(crate) fn prove_termination(
+    rules: &[(String, String)],
+    alphabet: &[String],
+    limits: &TerminationLimits,
+) -> TerminationResult {
+    let mut letters: Vec<char> = alphabet
+        .iter()
+        .flat_map(|letter| letter.chars())
+        .chain(rules.iter().flat_map(|(left, right)| left.chars().chain(right.chars())))
+        .collect();
+    letters.sort_by(|a, b| b.cmp(a));
+    letters.dedup();
+
+    let mut search = Search {
+        rules,
+        letters,
+        limits,
+        candidates: 0,
+    };
+    let certificate = search
+        .shortlex()
+        .or_else(|| search.weights())
+        .or_else(|| search.recursive_path());
+    match certificate {
+        Some(certificate) => TerminationResult::Proved(certificate),
+        None => TerminationResult::NotFound {
+            candidates: search.candidates.min(limits.max_candidates),
+        },
+    }
+}
+
+pub fn start_termination_prover(options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    let Some(rules) = rules_addition.read_raw_rules(&options.rules_path) else {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    };
+    info!("Rules read");
+
+    if let Some(equations) = rules_addition.equation_lines() {
+        println!("Equations ignored, the proof covers the rules only: {}", rules_addition.decode(&equations));
+    }
+    let alphabet = rules_addition.alphabet();
+    let limits = TerminationLimits::default();
+    match prove_termination(&rules, &alphabet, &limits) {
+        TerminationResult::Proved(certificate) => {
+            println!("Termination proved by {}", rules_addition.decode(&certificate.to_string()));
+            for (left, right) in &rules {
+                let explanation = format!(
+                    "{}: {}",
+                    RulesAddition::rule_line(left, right),
+                    certificate.explain(left, right)
+                );
+                println!("{}", rules_addition.decode(&explanation));
+            }
+        }
+        TerminationResult::NotFound { candidates } => {
+            println!("No termination proof found within {candidates} candidate orderings");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn equations_are_reported_as_not_covered() {
+        let rules_addition = system(&["a", "b"], "ordering: weight weights a=1 b=1\naa -> a\nab = ba\n");
+        assert_eq!(rules_addition.equation_lines().as_deref(), Some("ab = ba"));
+        assert!(system(&["a", "b"], "aa -> a\n").equation_lines().is_none());
+
+        let rules = [("aa".to_string(), "a".to_string())];
+        let alphabet = rules_addition.alphabet();
+        match prove_termination(&rules, &alphabet, &TerminationLimits::default()) {
+            TerminationResult::Proved(certificate) => assert_eq!(certificate.explain("aa", "a"), "|aa| = 2 > |a| = 1"),
+            TerminationResult::NotFound { .. } => panic!("aa -> a terminates"),
+        }
+    }
+
+    #[test]
+    fn length_increasing_rule_is_proved_by_rpo() {
+        let rules = [("ba".to_string(), "abb".to_string())];
+        let alphabet = ["a".to_string(), "b".to_string()];
+        match prove_termination(&rules, &alphabet, &TerminationLimits::default()) {
+            TerminationResult::Proved(certificate) => {
+                assert!(matches!(certificate, Certificate::RecursivePath(_)));
+                assert!(certificate.ordering().greater("ba", "abb"));
+            }
+            TerminationResult::NotFound { .. } => panic!("ba -> abb terminates"),
+        }
+    }
+}
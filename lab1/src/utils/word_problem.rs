@@ -0,0 +1,389 @@
+use crate::cli::{csv_writer, Format, Options};
+use crate::utils::invariants::{separate, InvariantLimits, Separation};
+use crate::utils::rules_additioner::RulesAddition;
+use crate::utils::srs::{occurrences, Srs};
+use log::info;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Forward,
+    Backward,
+}
+
+impl Direction {
+    fn reversed(self) -> Self {
+        match self {
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ProofStep {
+    pub(crate) left: String,
+    pub(crate) right: String,
+    pub(crate) position: usize,
+    pub(crate) direction: Direction,
+    pub(crate) word: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EqualityProof {
+    pub(crate) start: String,
+    pub(crate) steps: Vec<ProofStep>,
+}
+
+fn show(word: &str) -> &str {
+    if word.is_empty() { "ε" } else { word }
+}
+
+impl fmt::Display for EqualityProof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", show(&self.start))?;
+        for step in &self.steps {
+            let rule = RulesAddition::rule_line(&step.left, &step.right);
+            match step.direction {
+                Direction::Forward => write!(f, " --({rule} @{})--> {}", step.position, show(&step.word))?,
+                Direction::Backward => write!(f, " <--({rule} @{})-- {}", step.position, show(&step.word))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct WordProblemLimits {
+    pub(crate) max_word_len: usize,
+    pub(crate) max_states: usize,
+}
+
+impl Default for WordProblemLimits {
+    fn default() -> Self {
+        Self {
+            max_word_len: 20,
+            max_states: 200_000,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum WordProblem {
+    Equal(EqualityProof),
+    NotEqual { u_normal_form: String, v_normal_form: String },
+    FiniteClass { word: String, class_size: usize },
+    Separated(Separation),
+    Unknown { states: usize },
+}
+
+type Parents = HashMap<String, Option<(String, ProofStep)>>;
+
+impl RulesAddition {
+    pub(crate) fn normalize_with_steps(&self, word: &str) -> (String, Vec<ProofStep>) {
+        self.normalize_with_steps_counted(word, &mut 0, usize::MAX)
+            .expect("an unbounded budget is never exhausted")
+    }
+
+    // like normalize_counted, None once the shared budget of rewrite steps runs out
+    pub(crate) fn normalize_with_steps_counted(
+        &self,
+        word: &str,
+        count: &mut usize,
+        max_steps: usize,
+    ) -> Option<(String, Vec<ProofStep>)> {
+        let mut current = word.to_string();
+        let mut steps = vec![];
+        loop {
+            if *count >= max_steps {
+                return None;
+            }
+            if let Some((rule_id, position)) = self.leftmost_redex(&current) {
+                let (left, right) = (&self.srs.left_rules[rule_id], &self.srs.right_rules[rule_id]);
+                current = self.srs.rewrite_redex(&current, (rule_id, position));
+                steps.push(ProofStep {
+                    left: left.clone(),
+                    right: right.clone(),
+                    position,
+                    direction: Direction::Forward,
+                    word: current.clone(),
+                });
+            } else if let Some((step, next)) = self.equation_steps(&current).into_iter().next() {
+                steps.push(ProofStep {
+                    left: step.left,
+                    right: step.right,
+                    position: step.position,
+                    direction: Direction::Forward,
+                    word: next.clone(),
+                });
+                current = next;
+            } else {
+                return Some((current, steps));
+            }
+            *count += 1;
+        }
+    }
+
+    pub(crate) fn thue_neighbours(&self, word: &str) -> Vec<ProofStep> {
+        let mut neighbours = vec![];
+        let equations = self.srs.equations.iter().map(|(u, v)| (u, v));
+        for (left, right) in self.srs.left_rules.iter().zip(&self.srs.right_rules).chain(equations) {
+            for (from, to, direction) in [
+                (left, right, Direction::Forward),
+                (right, left, Direction::Backward),
+            ] {
+                let positions: Vec<usize> = if from.is_empty() {
+                    (0..=word.len()).filter(|&i| word.is_char_boundary(i)).collect()
+                } else {
+                    occurrences(word, from)
+                };
+                for position in positions {
+                    neighbours.push(ProofStep {
+                        left: left.clone(),
+                        right: right.clone(),
+                        position,
+                        direction,
+                        word: Srs::rewrite(word, position, from, to),
+                    });
+                }
+            }
+        }
+        neighbours
+    }
+
+    fn path_to(parents: &Parents, word: &str) -> Vec<ProofStep> {
+        let mut steps = vec![];
+        let mut current = word.to_string();
+        while let Some(Some((parent, step))) = parents.get(&current) {
+            steps.push(step.clone());
+            current = parent.clone();
+        }
+        steps.reverse();
+        steps
+    }
+
+    // turns v -> ... -> w into w -> ... -> v
+    fn reversed_steps(v: &str, steps: &[ProofStep]) -> Vec<ProofStep> {
+        (0..steps.len())
+            .rev()
+            .map(|i| ProofStep {
+                direction: steps[i].direction.reversed(),
+                word: if i == 0 { v.to_string() } else { steps[i - 1].word.clone() },
+                ..steps[i].clone()
+            })
+            .collect()
+    }
+
+    pub(crate) fn search_equal(&self, u: &str, v: &str, limits: &WordProblemLimits) -> WordProblem {
+        let max_word_len = limits.max_word_len.max(u.len()).max(v.len());
+        let mut sides: [(Parents, VecDeque<String>); 2] = [
+            (HashMap::from([(u.to_string(), None)]), VecDeque::from([u.to_string()])),
+            (HashMap::from([(v.to_string(), None)]), VecDeque::from([v.to_string()])),
+        ];
+        let mut truncated = [false, false];
+        if u == v {
+            return WordProblem::Equal(EqualityProof {
+                start: u.to_string(),
+                steps: vec![],
+            });
+        }
+
+        while sides.iter().all(|(_, frontier)| !frontier.is_empty()) {
+            let side = if sides[0].1.len() <= sides[1].1.len() { 0 } else { 1 };
+            let layer: Vec<String> = sides[side].1.drain(..).collect();
+            for word in layer {
+                for step in self.thue_neighbours(&word) {
+                    if step.word.len() > max_word_len {
+                        truncated[side] = true;
+                        continue;
+                    }
+                    if sides[side].0.contains_key(&step.word) {
+                        continue;
+                    }
+                    let next = step.word.clone();
+                    sides[side].0.insert(next.clone(), Some((word.clone(), step)));
+                    if sides[1 - side].0.contains_key(&next) {
+                        let mut steps = Self::path_to(&sides[0].0, &next);
+                        steps.extend(Self::reversed_steps(v, &Self::path_to(&sides[1].0, &next)));
+                        return WordProblem::Equal(EqualityProof {
+                            start: u.to_string(),
+                            steps,
+                        });
+                    }
+                    if sides[0].0.len() + sides[1].0.len() > limits.max_states {
+                        return WordProblem::Unknown {
+                            states: limits.max_states,
+                        };
+                    }
+                    sides[side].1.push_back(next);
+                }
+            }
+        }
+
+        // a class explored without hitting the length bound is the whole congruence class
+        for (side, word) in [u, v].into_iter().enumerate() {
+            if sides[side].1.is_empty() && !truncated[side] {
+                return WordProblem::FiniteClass {
+                    word: word.to_string(),
+                    class_size: sides[side].0.len(),
+                };
+            }
+        }
+        WordProblem::Unknown {
+            states: sides[0].0.len() + sides[1].0.len(),
+        }
+    }
+
+    pub(crate) fn decide_equal(&self, u: &str, v: &str, limits: &WordProblemLimits) -> WordProblem {
+        if self.is_complete() {
+            info!("System is complete, comparing normal forms");
+            let (u_normal_form, mut steps) = self.normalize_with_steps(u);
+            let (v_normal_form, v_steps) = self.normalize_with_steps(v);
+            if u_normal_form != v_normal_form {
+                return WordProblem::NotEqual {
+                    u_normal_form,
+                    v_normal_form,
+                };
+            }
+            steps.extend(Self::reversed_steps(v, &v_steps));
+            return WordProblem::Equal(EqualityProof {
+                start: u.to_string(),
+                steps,
+            });
+        }
+
+        info!("System is not known to be complete, searching the Thue congruence");
+        match self.search_equal(u, v, limits) {
+            WordProblem::Unknown { states } => {
+                info!("Search stopped after {states} words, looking for an invariant that tells them apart");
+                let mut rules = self.srs.rules();
+                rules.extend(self.srs.equations.iter().cloned());
+                match separate(&rules, self.srs.symbols.alphabet(), u, v, &InvariantLimits::default()) {
+                    Some(separation) => WordProblem::Separated(separation),
+                    None => WordProblem::Unknown { states },
+                }
+            }
+            result => result,
+        }
+    }
+}
+
+pub fn start_equal(u: &str, v: &str, options: &Options) -> Result<(), String> {
+    let mut rules_addition = RulesAddition::new();
+    rules_addition.read_letters(&options.alphabet_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read the alphabet {}", options.alphabet_path));
+    }
+    rules_addition.read_rules(&options.rules_path);
+    if rules_addition.error {
+        return Err(format!("Cannot read rules {}", options.rules_path));
+    }
+    let (u, v) = (rules_addition.encode(u)?, rules_addition.encode(v)?);
+    let (u, v) = (u.as_str(), v.as_str());
+
+    let result = rules_addition.decide_equal(u, v, &options.word_problem);
+    if options.format == Format::Csv {
+        let (verdict, detail) = match &result {
+            WordProblem::Equal(proof) => ("equal", proof.to_string()),
+            WordProblem::NotEqual {
+                u_normal_form,
+                v_normal_form,
+            } => ("not_equal", format!("{} {}", show(u_normal_form), show(v_normal_form))),
+            WordProblem::FiniteClass { word, class_size } => {
+                ("not_equal", format!("class of {} has {class_size} words", show(word)))
+            }
+            WordProblem::Separated(separation) => ("not_equal", separation.to_string()),
+            WordProblem::Unknown { states } => ("unknown", states.to_string()),
+        };
+        let mut writer = csv_writer();
+        let written = writer
+            .write_record(["u", "v", "verdict", "detail"])
+            .and_then(|_| {
+                writer.write_record([
+                    rules_addition.decode(u),
+                    rules_addition.decode(v),
+                    verdict.to_string(),
+                    rules_addition.decode(&detail),
+                ])
+            })
+            .and_then(|_| writer.flush().map_err(csv::Error::from));
+        if let Err(e) = written {
+            return Err(format!("Error while writing csv {e}"));
+        }
+        return Ok(());
+    }
+
+    let text = match result {
+        WordProblem::Equal(proof) => format!("{} = {}\n{proof}", show(u), show(v)),
+        WordProblem::NotEqual {
+            u_normal_form,
+            v_normal_form,
+        } => format!(
+            "{} != {}: normal forms {} and {} differ",
+            show(u),
+            show(v),
+            show(&u_normal_form),
+            show(&v_normal_form)
+        ),
+        WordProblem::FiniteClass { word, class_size } => format!(
+            "{} != {}: the class of {} has only {class_size} words",
+            show(u),
+            show(v),
+            show(&word)
+        ),
+        WordProblem::Separated(separation) => format!("{} != {}: {separation}", show(u), show(v)),
+        WordProblem::Unknown { states } => {
+            format!("Could not decide {} = {} within {states} words", show(u), show(v))
+        }
+    };
+    println!("{}", rules_addition.decode(&text));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::invariants::Invariant;
+    use crate::utils::rules_additioner::tests::system;
+
+    #[test]
+    fn equal_words_come_with_a_proof() {
+        let rules_addition = system(&["a", "b"], "ba -> ab\n");
+        let limits = WordProblemLimits::default();
+        match rules_addition.decide_equal("bab", "abb", &limits) {
+            WordProblem::Equal(proof) => assert_eq!(proof.to_string(), "bab --(ba -> ab @0)--> abb"),
+            other => panic!("bab = abb, got {other:?}"),
+        }
+        assert!(matches!(
+            rules_addition.decide_equal("ab", "aa", &limits),
+            WordProblem::NotEqual { .. }
+        ));
+    }
+
+    #[test]
+    fn equations_are_used_in_both_directions() {
+        let rules_addition = system(&["a", "b"], "ordering: weight weights a=1 b=1\nab = ba\n");
+        match rules_addition.decide_equal("aab", "baa", &WordProblemLimits::default()) {
+            WordProblem::Equal(proof) => {
+                assert_eq!(proof.start, "aab");
+                assert_eq!(proof.steps.last().map(|step| step.word.as_str()), Some("baa"));
+            }
+            other => panic!("aab = baa, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn invariants_decide_what_the_search_cannot() {
+        // the weight cannot orient the rule, so the system is not known to be complete
+        let rules_addition = system(&["a", "b"], "ordering: weight weights a=1 b=1\nba -> ab\n");
+        let limits = WordProblemLimits {
+            max_word_len: 3,
+            max_states: 2,
+        };
+        match rules_addition.decide_equal("aab", "abb", &limits) {
+            WordProblem::Separated(separation) => assert!(matches!(separation.invariant, Invariant::Linear(_))),
+            other => panic!("aab != abb, got {other:?}"),
+        }
+    }
+}
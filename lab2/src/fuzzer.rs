@@ -1,6 +1,8 @@
 use log::{error, info};
+use crate::seed::read_seed;
 use rand::Rng;
-use rand::rngs::ThreadRng;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use regex::Regex;
 use std::collections::HashSet;
 
@@ -20,11 +22,11 @@ struct Fuzzer {
     afa: Vec<(usize, usize, usize)>,
     re: Regex,
     ext_re: Regex,
-    rng: ThreadRng,
+    rng: StdRng,
 }
 
 impl Fuzzer {
-    fn new() -> Self {
+    fn new(seed: u64) -> Self {
         Self {
             tests_count: 1000,
             min_str_len: 10,
@@ -321,7 +323,7 @@ impl Fuzzer {
             ],
             re: Regex::new("^(aa|bb|cc)*b(aaa|bbb)*((ab|bc|ccc)*aa)*abc(a|b|c)(b|)$").unwrap(),
             ext_re: Regex::new("^(aa|bb|cc)*b(aaa|bbb)*((ab|bc|ccc)*aa)*abc[abc]b?$").unwrap(),
-            rng: rand::rng(),
+            rng: StdRng::seed_from_u64(seed),
         }
     }
 
@@ -416,7 +418,15 @@ impl Fuzzer {
 }
 
 pub fn start_fuzzer() {
-    let mut new_fuzzer = Fuzzer::new();
+    let seed = match read_seed() {
+        Ok(seed) => seed,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    println!("Seed: {seed}");
+    let mut new_fuzzer = Fuzzer::new(seed);
     let mut test_passed: usize = 0;
 
     for _ in 0..new_fuzzer.tests_count {
@@ -1,6 +1,8 @@
 use crate::fuzzer::start_fuzzer;
 
 mod fuzzer;
+#[path = "../../shared/seed.rs"]
+mod seed;
 
 fn main() {
     env_logger::init();
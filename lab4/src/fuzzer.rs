@@ -3,25 +3,27 @@ use std::fs::File;
 use std::io::BufWriter;
 use std::time::Instant;
 use rand::Rng;
-use rand::rngs::ThreadRng;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use log::{error, info};
+use crate::seed::read_seed;
 
 struct Fuzzer {
     words_count_in_lang: usize,
     words_count_not_in_lang: usize,
     min_str_len: usize,
     max_str_len: usize,
-    rng: ThreadRng,
+    rng: StdRng,
 }
 
 impl Fuzzer {
-    fn new() -> Self {
+    fn new(seed: u64) -> Self {
         Self {
             words_count_in_lang: 1000,
             words_count_not_in_lang: 1000,
             min_str_len: 100,
             max_str_len: 5000,
-            rng: rand::rng(),
+            rng: StdRng::seed_from_u64(seed),
         }
     }
 
@@ -247,7 +249,15 @@ impl Fuzzer {
 }
 
 pub fn start_fuzzer() {
-    let mut fuzzer = Fuzzer::new();
+    let seed = match read_seed() {
+        Ok(seed) => seed,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    println!("Seed: {seed}");
+    let mut fuzzer = Fuzzer::new(seed);
 
     let buckets_count = 30;
     let min_len = fuzzer.min_str_len;
@@ -408,8 +418,11 @@ pub fn start_fuzzer() {
     );
 
     info!("Wrote info: in_times.csv and out_times.csv");
+    info!("Seed {seed} replays the words and counts, the timings are measured anew");
 }
 
+// the seed fixes the words, so bucket and count columns repeat between runs with the same seed;
+// avg_naive_sec and avg_opt_sec are wall-clock timings and are not reproducible
 fn write_csv(
     filename: &str,
     min_len: usize,
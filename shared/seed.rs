@@ -0,0 +1,14 @@
+// shared by the lab2 and lab4 fuzzers, each includes this file as its `seed` module
+
+// --seed <n> or FUZZ_SEED replays an earlier run, otherwise a fresh seed is drawn
+pub fn read_seed() -> Result<u64, String> {
+    let args: Vec<String> = std::env::args().collect();
+    let value = match args.iter().position(|arg| arg == "--seed") {
+        Some(i) => args.get(i + 1).cloned().ok_or("--seed needs a value")?,
+        None => match std::env::var("FUZZ_SEED") {
+            Ok(value) => value,
+            Err(_) => return Ok(rand::random()),
+        },
+    };
+    value.parse().map_err(|e| format!("invalid seed `{value}`: {e}"))
+}